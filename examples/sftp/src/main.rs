@@ -14,11 +14,14 @@ use ssh2::{Session, Sftp};
 use thiserror::Error;
 use widestring::{u16str, U16String};
 use wincs::{
-    ext::{ConvertOptions, FileExt},
-    filter::{info, ticket, SyncFilter},
+    ext::{ConvertOptions, FileExt, PathExt},
+    filter::{
+        info::{self, RenameKind},
+        ticket, SyncFilter,
+    },
     placeholder_file::{Metadata, PlaceholderFile},
     request::Request,
-    CloudErrorKind, PopulationType, Registration, SecurityId, SyncRootIdBuilder,
+    CloudErrorKind, FileTime, PopulationType, Registration, SecurityId, SyncRootIdBuilder,
 };
 
 // max should be 65536, this is done both in term-scp and sshfs because it's the
@@ -67,6 +70,7 @@ fn main() {
             )
             .version(u16str!("1.0.0"))
             .recycle_bin_uri(u16str!("http://cloudmirror.example.com/recyclebin"))
+            .unwrap()
             .register(Path::new(&client_path))
             .unwrap();
     }
@@ -105,8 +109,11 @@ fn convert_to_placeholder(path: &Path) {
             ConvertOptions::default()
         };
 
-        let file = open_options.open(entry.path()).unwrap();
-        file.to_placeholder(convert_options).unwrap();
+        // skip entries that were already converted by a previous run
+        if entry.path().needs_conversion().unwrap() {
+            let file = open_options.open(entry.path()).unwrap();
+            file.to_placeholder(convert_options).unwrap();
+        }
 
         if is_dir {
             convert_to_placeholder(&entry.path());
@@ -245,57 +252,45 @@ impl SyncFilter for Filter {
     fn delete(&self, request: Request, ticket: ticket::Delete, info: info::Delete) {
         println!("delete {:?}", request.path());
         let path = Path::new(unsafe { OsStr::from_encoded_bytes_unchecked(request.file_blob()) });
-        let res = || -> Result<(), _> {
-            match info.is_directory() {
-                true => self
-                    .remove_dir_all(path)
-                    .map_err(|_| CloudErrorKind::InvalidRequest)?,
-                false => self
-                    .sftp
-                    .unlink(path)
-                    .map_err(|_| CloudErrorKind::InvalidRequest)?,
-            }
-            ticket.pass().unwrap();
-            Ok(())
-        }();
-
-        if let Err(e) = res {
-            ticket.fail(e).unwrap();
-        }
+        ticket
+            .pass_after(|| {
+                match info.is_directory() {
+                    true => self.remove_dir_all(path),
+                    false => self.sftp.unlink(path),
+                }
+                .map_err(|_| CloudErrorKind::InvalidRequest)
+            })
+            .unwrap();
     }
 
     // TODO: Do I have to move the file and set the file progress? or does the OS
     // handle that? (I think I do)
     fn rename(&self, request: Request, ticket: ticket::Rename, info: info::Rename) {
         let res = || -> Result<(), _> {
-            match info.target_in_scope() {
-                true => {
-                    // TODO: path should auto include the drive letter
-                    let src = request.path();
-                    // TODO: should be relative
-                    let dest = info.target_path();
-
-                    match info.source_in_scope() {
-                        // TODO: use fs::copy or fs::rename, whatever it is to move the local files,
-                        // then use ConvertToPlaceholder. I'm not sure if I have to do this recursively
-                        // for each file or only the top-level folder TODO: which
-                        // rename flags do I use? how do I know if I should be overwriting?
-                        true => self
-                            .sftp
-                            .rename(&src, &dest, None)
-                            .map_err(|_| CloudErrorKind::InvalidRequest)?,
-                        false => match info.is_directory() {
-                            true => self
-                                .create_dir_all(&src, &dest)
-                                .map_err(|_| CloudErrorKind::InvalidRequest)?,
-                            false => self
-                                .create_file(&src, &dest)
-                                .map_err(|_| CloudErrorKind::InvalidRequest)?,
-                        },
-                    }
-                }
+            // TODO: path should auto include the drive letter
+            let src = request.path();
+            // TODO: should be relative
+            let dest = info.target_path();
+
+            match info.kind() {
+                // TODO: use fs::copy or fs::rename, whatever it is to move the local files,
+                // then use ConvertToPlaceholder. I'm not sure if I have to do this recursively
+                // for each file or only the top-level folder TODO: which
+                // rename flags do I use? how do I know if I should be overwriting?
+                RenameKind::WithinRoot => self
+                    .sftp
+                    .rename(&src, &dest, None)
+                    .map_err(|_| CloudErrorKind::InvalidRequest)?,
+                RenameKind::IntoRoot => match info.is_directory() {
+                    true => self
+                        .create_dir_all(&src, &dest)
+                        .map_err(|_| CloudErrorKind::InvalidRequest)?,
+                    false => self
+                        .create_file(&src, &dest)
+                        .map_err(|_| CloudErrorKind::InvalidRequest)?,
+                },
                 // TODO: do I need to delete it locally?
-                false => self
+                RenameKind::OutOfRoot | RenameKind::Outside => self
                     .sftp
                     .unlink(Path::new(unsafe {
                         OsStr::from_encoded_bytes_unchecked(request.file_blob())
@@ -335,7 +330,11 @@ impl SyncFilter for Filter {
                 println!("is file: {}, is dir: {}", stat.is_file(), stat.is_dir());
 
                 let relative_path = path.strip_prefix(parent).unwrap();
+                let unix_time = |time: Option<u64>| {
+                    FileTime::from_unix_time(time.unwrap_or_default() as i64).unwrap_or_default()
+                };
                 PlaceholderFile::new(relative_path)
+                    .unwrap()
                     .metadata(
                         if stat.is_dir() {
                             Metadata::directory()
@@ -344,9 +343,9 @@ impl SyncFilter for Filter {
                         }
                         .size(stat.size.unwrap_or_default())
                         // .creation_time() // either the access time or write time, whichever is less
-                        .last_access_time(stat.atime.unwrap_or_default())
-                        .last_write_time(stat.mtime.unwrap_or_default())
-                        .change_time(stat.mtime.unwrap_or_default()),
+                        .last_access_time(unix_time(stat.atime))
+                        .last_write_time(unix_time(stat.mtime))
+                        .change_time(unix_time(stat.mtime)),
                     )
                     .overwrite()
                     // .mark_sync() // need this?
@@ -0,0 +1,178 @@
+use std::{
+    fmt, mem,
+    ops::{Deref, DerefMut},
+    sync::{Arc, Mutex},
+};
+
+/// A source of the buffers used to transfer data to placeholders, e.g. by
+/// [FetchData::transfer_from][crate::ticket::FetchData::transfer_from].
+///
+/// A pool could be shared across every callback of a connection through
+/// [Session::buffer_pool][crate::Session::buffer_pool], reducing the amount of allocations when
+/// many placeholders are hydrated concurrently.
+pub trait BufferPool: Send + Sync {
+    /// Returns a buffer of exactly `len` bytes, where `len` is always a multiple of 4KiB. The
+    /// contents of the buffer are unspecified.
+    fn acquire(&self, len: usize) -> Vec<u8>;
+
+    /// Returns a buffer to the pool once the transfer is complete.
+    fn release(&self, buffer: Vec<u8>);
+}
+
+/// A [BufferPool][crate::BufferPool] that keeps up to a fixed amount of released buffers for
+/// reuse.
+///
+/// Released buffers are kept until the pool is dropped, so the pool holds on to at most
+/// `max_buffers` times the size of the largest buffer, e.g. 16MiB with the default of 16 buffers
+/// and the 1MiB buffers used by [FetchData::transfer_from][crate::ticket::FetchData::transfer_from].
+#[derive(Debug)]
+pub struct VecPool {
+    buffers: Mutex<Vec<Vec<u8>>>,
+    max_buffers: usize,
+}
+
+impl VecPool {
+    /// Creates a new [VecPool][crate::VecPool] that keeps at most `max_buffers` released buffers,
+    /// any more are freed.
+    pub fn new(max_buffers: usize) -> Self {
+        Self {
+            buffers: Mutex::new(Vec::new()),
+            max_buffers,
+        }
+    }
+}
+
+/// Keeps at most 16 released buffers.
+impl Default for VecPool {
+    fn default() -> Self {
+        Self::new(16)
+    }
+}
+
+impl BufferPool for VecPool {
+    fn acquire(&self, len: usize) -> Vec<u8> {
+        let mut buffer = self.buffers.lock().unwrap().pop().unwrap_or_default();
+        buffer.resize(len, 0);
+        buffer
+    }
+
+    fn release(&self, buffer: Vec<u8>) {
+        let mut buffers = self.buffers.lock().unwrap();
+        if buffers.len() < self.max_buffers {
+            buffers.push(buffer);
+        }
+    }
+}
+
+/// A buffer that is returned to its pool when dropped.
+pub(crate) struct Pooled<'a> {
+    buffer: Vec<u8>,
+    pool: Option<&'a dyn BufferPool>,
+}
+
+impl<'a> Pooled<'a> {
+    /// Acquires a buffer from the pool, or allocates one if there is no pool.
+    pub(crate) fn acquire(pool: Option<&'a Arc<dyn BufferPool>>, len: usize) -> Self {
+        let pool = pool.map(|pool| &**pool);
+        Self {
+            buffer: pool.map_or_else(|| vec![0; len], |pool| pool.acquire(len)),
+            pool,
+        }
+    }
+}
+
+impl Deref for Pooled<'_> {
+    type Target = [u8];
+
+    fn deref(&self) -> &Self::Target {
+        &self.buffer
+    }
+}
+
+impl DerefMut for Pooled<'_> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.buffer
+    }
+}
+
+impl fmt::Debug for Pooled<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Pooled")
+            .field("len", &self.buffer.len())
+            .field("pool", &self.pool.is_some())
+            .finish()
+    }
+}
+
+impl Drop for Pooled<'_> {
+    fn drop(&mut self) {
+        if let Some(pool) = self.pool {
+            pool.release(mem::take(&mut self.buffer));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+
+    // counts the buffers that had to be allocated rather than reused
+    #[derive(Default)]
+    struct CountingPool {
+        pool: VecPool,
+        allocated: AtomicUsize,
+    }
+
+    impl BufferPool for CountingPool {
+        fn acquire(&self, len: usize) -> Vec<u8> {
+            if self.pool.buffers.lock().unwrap().is_empty() {
+                self.allocated.fetch_add(1, Ordering::Relaxed);
+            }
+            self.pool.acquire(len)
+        }
+
+        fn release(&self, buffer: Vec<u8>) {
+            self.pool.release(buffer);
+        }
+    }
+
+    #[test]
+    fn pooled_buffers_are_reused() {
+        let counting = Arc::new(CountingPool::default());
+        let pool: Arc<dyn BufferPool> = counting.clone();
+
+        let first = Pooled::acquire(Some(&pool), 4096).as_ptr();
+        // sequential transfers keep reusing the same released buffer
+        for _ in 0..8 {
+            let buffer = Pooled::acquire(Some(&pool), 4096);
+            assert_eq!(buffer.len(), 4096);
+            assert_eq!(buffer.as_ptr(), first);
+        }
+        assert_eq!(counting.allocated.load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn pooled_buffers_are_resized() {
+        let pool: Arc<dyn BufferPool> = Arc::new(VecPool::default());
+        drop(Pooled::acquire(Some(&pool), 8192));
+        assert_eq!(Pooled::acquire(Some(&pool), 4096).len(), 4096);
+        assert_eq!(Pooled::acquire(Some(&pool), 16384).len(), 16384);
+    }
+
+    #[test]
+    fn vec_pool_keeps_at_most_max_buffers() {
+        let pool = VecPool::new(2);
+        for _ in 0..4 {
+            pool.release(vec![0; 4096]);
+        }
+        assert_eq!(pool.buffers.lock().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn unpooled_buffers_are_allocated() {
+        let buffer = Pooled::acquire(None, 4096);
+        assert_eq!(&*buffer, &[0; 4096][..]);
+    }
+}
@@ -19,6 +19,7 @@ use crate::{
     placeholder_file::{Metadata, PlaceholderFile},
     request::{RawConnectionKey, RawTransferKey},
     usn::Usn,
+    utility::FileRange,
 };
 
 /// Read data from a placeholder file.
@@ -135,8 +136,10 @@ impl Command for Update<'_> {
                 } else {
                     CloudFilters::CF_OPERATION_RESTART_HYDRATION_FLAG_NONE
                 },
-                FsMetadata: self.metadata.map_or(ptr::null_mut(), |mut metadata| {
-                    &mut metadata as *mut _ as *mut _
+                // point to the metadata within the command, rather than a copy that is dropped
+                // before the operation is executed
+                FsMetadata: self.metadata.as_ref().map_or(ptr::null_mut(), |metadata| {
+                    &metadata.0 as *const _ as *mut _
                 }),
                 FileIdentity: self
                     .blob
@@ -154,6 +157,9 @@ pub struct CreatePlaceholders<'a> {
     pub placeholders: &'a mut [PlaceholderFile],
     /// The total amount of placeholders that are a child of the current directory.
     pub total: u64,
+    /// Whether or not the directory is fully populated after the placeholders are created. If
+    /// `false`, the operating system will request the placeholders again on the next enumeration.
+    pub complete: bool,
 }
 
 impl Command for CreatePlaceholders<'_> {
@@ -186,9 +192,10 @@ impl Command for CreatePlaceholders<'_> {
     fn build(&self) -> CF_OPERATION_PARAMETERS_0 {
         CF_OPERATION_PARAMETERS_0 {
             TransferPlaceholders: CF_OPERATION_PARAMETERS_0_7 {
-                // TODO: this flag tells the system there are no more placeholders in this directory (when that can be untrue)
-                //       in the future, implement streaming
-                Flags: CloudFilters::CF_OPERATION_TRANSFER_PLACEHOLDERS_FLAG_DISABLE_ON_DEMAND_POPULATION,
+                Flags: match self.complete {
+                    true => CloudFilters::CF_OPERATION_TRANSFER_PLACEHOLDERS_FLAG_DISABLE_ON_DEMAND_POPULATION,
+                    false => CloudFilters::CF_OPERATION_TRANSFER_PLACEHOLDERS_FLAG_NONE,
+                },
                 CompletionStatus: Foundation::STATUS_SUCCESS,
                 PlaceholderTotalCount: self.total as i64,
                 PlaceholderArray: match self.placeholders.is_empty() {
@@ -197,6 +204,9 @@ impl Command for CreatePlaceholders<'_> {
                     false => self.placeholders.as_ptr() as *mut _,
                 },
                 PlaceholderCount: self.placeholders.len() as u32,
+                // an output parameter filled by the operating system with the number of
+                // placeholders processed from the array, the individual results are read from the
+                // placeholders themselves
                 EntriesProcessed: 0,
             },
         }
@@ -231,9 +241,24 @@ impl Fallible for CreatePlaceholders<'_> {
 #[derive(Debug)]
 pub struct Validate {
     /// The range of data to validate as "good."
+    ///
+    /// The end of the range must not exceed `i64::MAX`.
     pub range: Range<u64>,
 }
 
+impl Validate {
+    fn build_range(range: FileRange) -> CF_OPERATION_PARAMETERS_0 {
+        CF_OPERATION_PARAMETERS_0 {
+            AckData: CF_OPERATION_PARAMETERS_0_0 {
+                Flags: CloudFilters::CF_OPERATION_ACK_DATA_FLAG_NONE,
+                CompletionStatus: Foundation::STATUS_SUCCESS,
+                Offset: range.offset,
+                Length: range.length,
+            },
+        }
+    }
+}
+
 impl Command for Validate {
     const OPERATION: CF_OPERATION_TYPE = CloudFilters::CF_OPERATION_TYPE_ACK_DATA;
 
@@ -242,15 +267,20 @@ impl Command for Validate {
 
     unsafe fn result(_info: CF_OPERATION_PARAMETERS_0) -> Self::Result {}
 
+    /// Panics if the range is invalid, use [Validate::execute][crate::command::Validate::execute]
+    /// to receive an error instead.
     fn build(&self) -> CF_OPERATION_PARAMETERS_0 {
-        CF_OPERATION_PARAMETERS_0 {
-            AckData: CF_OPERATION_PARAMETERS_0_0 {
-                Flags: CloudFilters::CF_OPERATION_ACK_DATA_FLAG_NONE,
-                CompletionStatus: Foundation::STATUS_SUCCESS,
-                Offset: self.range.start as i64,
-                Length: self.range.end as i64,
-            },
-        }
+        Self::build_range(FileRange::new(self.range.clone()).expect("the range must be valid"))
+    }
+
+    /// Returns `E_INVALIDARG` if the range is reversed or its end exceeds `i64::MAX`.
+    fn execute(
+        &self,
+        connection_key: RawConnectionKey,
+        transfer_key: RawTransferKey,
+    ) -> core::Result<Self::Result> {
+        let range = FileRange::new(self.range.clone())?;
+        execute::<Self>(Self::build_range(range), connection_key, transfer_key)
     }
 }
 
@@ -51,6 +51,23 @@ pub trait Command: Sized {
     ) -> core::Result<Self::Result> {
         execute::<Self>(self.build(), connection_key, transfer_key)
     }
+
+    /// Executes the command to the platform, mapping a failure to its
+    /// [CloudErrorKind][crate::CloudErrorKind].
+    ///
+    /// This is useful for deciding whether or not to retry a command, e.g. after a
+    /// [CloudErrorKind::RequestTimeout][crate::CloudErrorKind::RequestTimeout]. Errors that are
+    /// not predefined cloud file errors are mapped to
+    /// [CloudErrorKind::Unsuccessful][crate::CloudErrorKind::Unsuccessful], use
+    /// [Command::execute][crate::command::Command::execute] to access the raw error.
+    fn execute_checked(
+        &self,
+        connection_key: RawConnectionKey,
+        transfer_key: RawTransferKey,
+    ) -> Result<Self::Result, CloudErrorKind> {
+        self.execute(connection_key, transfer_key)
+            .map_err(|err| CloudErrorKind::from_error(&err).unwrap_or(CloudErrorKind::Unsuccessful))
+    }
 }
 
 pub fn execute<C: Command>(
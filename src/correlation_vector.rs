@@ -0,0 +1,79 @@
+use std::fmt::Debug;
+
+use windows::{
+    core::{self, GUID},
+    Win32::{
+        Foundation::NTSTATUS,
+        System::CorrelationVector::{
+            RtlExtendCorrelationVector, RtlIncrementCorrelationVector,
+            RtlInitializeCorrelationVector, CORRELATION_VECTOR,
+        },
+    },
+};
+
+/// A correlation vector used to trace an operation across the sync provider and the operating
+/// system.
+///
+/// A correlation vector could be read from a [Request][crate::Request] or attached to a
+/// placeholder through [FileExt::set_correlation_vector][crate::ext::FileExt::set_correlation_vector].
+/// Correlation vectors are supported on Windows 10 version 1803 (build 17134) and later.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct CorrelationVector(pub(crate) CORRELATION_VECTOR);
+
+impl CorrelationVector {
+    // version 2 vectors have a longer base and support more components
+    const VERSION_2: i32 = 2;
+
+    /// Creates a new version 2 [CorrelationVector][crate::CorrelationVector] with its base
+    /// derived from the given GUID.
+    pub fn new(guid: GUID) -> core::Result<Self> {
+        let mut vector = CORRELATION_VECTOR::default();
+        ok(unsafe {
+            RtlInitializeCorrelationVector(
+                &mut vector as *mut _,
+                Self::VERSION_2,
+                &guid as *const _,
+            )
+        })
+        .map(|_| Self(vector))
+    }
+
+    /// Increments the last component of the correlation vector, used for each subsequent
+    /// operation.
+    pub fn increment(&mut self) -> core::Result<()> {
+        ok(unsafe { RtlIncrementCorrelationVector(&mut self.0 as *mut _) })
+    }
+
+    /// Appends a new component to the correlation vector, used when an operation is passed to
+    /// another component.
+    pub fn extend(&mut self) -> core::Result<()> {
+        ok(unsafe { RtlExtendCorrelationVector(&mut self.0 as *mut _) })
+    }
+
+    /// The version of the correlation vector.
+    pub fn version(&self) -> u8 {
+        self.0.Version.0
+    }
+
+    /// The correlation vector as a string of base64 characters.
+    pub fn as_bytes(&self) -> &[u8] {
+        // Safety: `CHAR` is a transparent wrapper around a `u8`
+        let vector = unsafe { &*(&self.0.Vector as *const _ as *const [u8; 129]) };
+        let len = vector.iter().position(|&c| c == 0).unwrap_or(vector.len());
+        &vector[..len]
+    }
+}
+
+impl Debug for CorrelationVector {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CorrelationVector")
+            .field("version", &self.version())
+            .field("vector", &String::from_utf8_lossy(self.as_bytes()))
+            .finish()
+    }
+}
+
+// the Rtl* functions return an NTSTATUS, which the bindings declare as a u32
+fn ok(status: u32) -> core::Result<()> {
+    NTSTATUS(status as i32).ok()
+}
@@ -1,7 +1,10 @@
-use windows::Win32::Foundation::{self, NTSTATUS};
+use windows::{
+    core,
+    Win32::Foundation::{self, NTSTATUS, WIN32_ERROR},
+};
 
 /// Predefined error types provided by the operating system.
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum CloudErrorKind {
     /// Access to the cloud file is denied.
     AccessDenied,
@@ -129,3 +132,92 @@ impl From<CloudErrorKind> for NTSTATUS {
         }
     }
 }
+
+impl CloudErrorKind {
+    /// Gets the [CloudErrorKind][crate::CloudErrorKind] corresponding to the error, if it is one
+    /// of the predefined cloud file errors.
+    pub fn from_error(error: &core::Error) -> Option<Self> {
+        error
+            .win32_error()
+            .and_then(|error| Self::try_from(error).ok())
+    }
+}
+
+impl TryFrom<WIN32_ERROR> for CloudErrorKind {
+    type Error = WIN32_ERROR;
+
+    fn try_from(error: WIN32_ERROR) -> Result<Self, Self::Error> {
+        match error {
+            Foundation::ERROR_CLOUD_FILE_ACCESS_DENIED => Ok(Self::AccessDenied),
+            Foundation::ERROR_CLOUD_FILE_ALREADY_CONNECTED => Ok(Self::AlreadyConnected),
+            Foundation::ERROR_CLOUD_FILE_AUTHENTICATION_FAILED => Ok(Self::AuthenticationFailed),
+            Foundation::ERROR_CLOUD_FILE_CONNECTED_PROVIDER_ONLY => Ok(Self::ConnectedProviderOnly),
+            Foundation::ERROR_CLOUD_FILE_DEHYDRATION_DISALLOWED => Ok(Self::DehydrationDisallowed),
+            Foundation::ERROR_CLOUD_FILE_IN_USE => Ok(Self::InUse),
+            Foundation::ERROR_CLOUD_FILE_INCOMPATIBLE_HARDLINKS => Ok(Self::IncompatibleHardlinks),
+            Foundation::ERROR_CLOUD_FILE_INSUFFICIENT_RESOURCES => Ok(Self::InsufficientResources),
+            Foundation::ERROR_CLOUD_FILE_INVALID_REQUEST => Ok(Self::InvalidRequest),
+            Foundation::ERROR_CLOUD_FILE_METADATA_CORRUPT => Ok(Self::MetadataCorrupt),
+            Foundation::ERROR_CLOUD_FILE_METADATA_TOO_LARGE => Ok(Self::MetadataTooLarge),
+            Foundation::ERROR_CLOUD_FILE_NETWORK_UNAVAILABLE => Ok(Self::NetworkUnavailable),
+            Foundation::ERROR_CLOUD_FILE_NOT_IN_SYNC => Ok(Self::NotInSync),
+            Foundation::ERROR_CLOUD_FILE_NOT_SUPPORTED => Ok(Self::NotSupported),
+            Foundation::ERROR_CLOUD_FILE_NOT_UNDER_SYNC_ROOT => Ok(Self::NotUnderSyncRoot),
+            Foundation::ERROR_CLOUD_FILE_PINNED => Ok(Self::Pinned),
+            Foundation::ERROR_CLOUD_FILE_PROPERTY_BLOB_CHECKSUM_MISMATCH => {
+                Ok(Self::PropertyBlobChecksumMismatch)
+            }
+            Foundation::ERROR_CLOUD_FILE_PROPERTY_BLOB_TOO_LARGE => Ok(Self::PropertyBlobTooLarge),
+            Foundation::ERROR_CLOUD_FILE_PROPERTY_CORRUPT => Ok(Self::PropertyCorrupt),
+            Foundation::ERROR_CLOUD_FILE_PROPERTY_LOCK_CONFLICT => Ok(Self::PropertyLockConflict),
+            Foundation::ERROR_CLOUD_FILE_PROPERTY_VERSION_NOT_SUPPORTED => {
+                Ok(Self::PropertyVersionNotSupported)
+            }
+            Foundation::ERROR_CLOUD_FILE_PROVIDER_NOT_RUNNING => Ok(Self::ProviderNotRunning),
+            Foundation::ERROR_CLOUD_FILE_PROVIDER_TERMINATED => Ok(Self::ProviderTerminated),
+            Foundation::ERROR_CLOUD_FILE_READ_ONLY_VOLUME => Ok(Self::ReadOnlyVolume),
+            Foundation::ERROR_CLOUD_FILE_REQUEST_ABORTED => Ok(Self::RequestAborted),
+            Foundation::ERROR_CLOUD_FILE_REQUEST_CANCELED => Ok(Self::RequestCancelled),
+            Foundation::ERROR_CLOUD_FILE_REQUEST_TIMEOUT => Ok(Self::RequestTimeout),
+            Foundation::ERROR_CLOUD_FILE_SYNC_ROOT_METADATA_CORRUPT => {
+                Ok(Self::SyncRootMetadataCorrupt)
+            }
+            Foundation::ERROR_CLOUD_FILE_TOO_MANY_PROPERTY_BLOBS => Ok(Self::TooManyPropertyBlobs),
+            Foundation::ERROR_CLOUD_FILE_UNSUCCESSFUL => Ok(Self::Unsuccessful),
+            Foundation::ERROR_CLOUD_FILE_VALIDATION_FAILED => Ok(Self::ValidationFailed),
+            error => Err(error),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use windows::core::HRESULT;
+
+    use super::*;
+
+    fn error(code: WIN32_ERROR) -> core::Error {
+        HRESULT::from(code).into()
+    }
+
+    #[test]
+    fn from_error_maps_request_aborted() {
+        assert_eq!(
+            CloudErrorKind::from_error(&error(Foundation::ERROR_CLOUD_FILE_REQUEST_ABORTED)),
+            Some(CloudErrorKind::RequestAborted)
+        );
+        assert_eq!(
+            CloudErrorKind::from_error(&error(Foundation::ERROR_CLOUD_FILE_REQUEST_TIMEOUT)),
+            Some(CloudErrorKind::RequestTimeout)
+        );
+    }
+
+    #[test]
+    fn from_error_ignores_other_errors() {
+        assert_eq!(
+            CloudErrorKind::from_error(&error(Foundation::ERROR_ACCESS_DENIED)),
+            None
+        );
+        assert_eq!(CloudErrorKind::from_error(&Foundation::E_FAIL.into()), None);
+    }
+}
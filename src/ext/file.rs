@@ -1,26 +1,33 @@
 use std::{
     fs::File,
-    mem::{self, MaybeUninit},
-    ops::{Bound, Range, RangeBounds},
-    os::windows::{io::AsRawHandle, prelude::RawHandle},
+    io::Write,
+    iter,
+    mem::{self, ManuallyDrop, MaybeUninit},
+    ops::{Range, RangeBounds},
+    os::windows::{
+        fs::FileExt as StdFileExt,
+        io::{AsRawHandle, FromRawHandle},
+        prelude::RawHandle,
+    },
     ptr,
 };
 
+use memoffset::offset_of;
 use widestring::U16CStr;
 use windows::{
     core,
     Win32::{
-        Foundation::HANDLE,
+        Foundation::{ERROR_MORE_DATA, E_INVALIDARG, HANDLE},
         Storage::{
             CloudFilters::{
-                self, CfConvertToPlaceholder, CfDehydratePlaceholder, CfGetPlaceholderInfo,
-                CfGetPlaceholderRangeInfo, CfGetPlaceholderStateFromFileInfo,
+                self, CfConvertToPlaceholder, CfDehydratePlaceholder, CfGetCorrelationVector,
+                CfGetPlaceholderInfo, CfGetPlaceholderRangeInfo, CfGetPlaceholderStateFromFileInfo,
                 CfGetSyncRootInfoByHandle, CfHydratePlaceholder, CfRevertPlaceholder,
-                CfSetInSyncState, CfSetPinState, CfUpdatePlaceholder, CF_CONVERT_FLAGS,
-                CF_FILE_RANGE, CF_PIN_STATE, CF_PLACEHOLDER_RANGE_INFO_CLASS,
-                CF_PLACEHOLDER_STANDARD_INFO, CF_PLACEHOLDER_STATE, CF_SET_PIN_FLAGS,
-                CF_SYNC_PROVIDER_STATUS, CF_SYNC_ROOT_INFO_STANDARD, CF_SYNC_ROOT_STANDARD_INFO,
-                CF_UPDATE_FLAGS,
+                CfSetCorrelationVector, CfSetInSyncState, CfSetPinState, CfUpdatePlaceholder,
+                CF_CONVERT_FLAGS, CF_DEHYDRATE_FLAGS, CF_FILE_RANGE, CF_PIN_STATE,
+                CF_PLACEHOLDER_RANGE_INFO_CLASS, CF_PLACEHOLDER_STANDARD_INFO,
+                CF_PLACEHOLDER_STATE, CF_SET_PIN_FLAGS, CF_SYNC_PROVIDER_STATUS,
+                CF_SYNC_ROOT_INFO_STANDARD, CF_SYNC_ROOT_STANDARD_INFO, CF_UPDATE_FLAGS,
             },
             FileSystem::{self, GetFileInformationByHandleEx, FILE_ATTRIBUTE_TAG_INFO},
         },
@@ -28,11 +35,17 @@ use windows::{
 };
 
 use crate::{
+    correlation_vector::CorrelationVector,
     placeholder_file::Metadata,
+    platform,
     root::{HydrationPolicy, HydrationType, PopulationType, SupportedAttributes},
     usn::Usn,
+    utility::{io_to_core, FileRange},
 };
 
+/// The size of the chunks read by [FileExt::read_modified_to][crate::ext::FileExt::read_modified_to].
+const MODIFIED_CHUNK_SIZE: usize = 65536;
+
 /// An API extension to [File][std::fs::File].
 pub trait FileExt: AsRawHandle {
     /// Converts a file to a placeholder file, returning the resulting USN.
@@ -87,7 +100,9 @@ pub trait FileExt: AsRawHandle {
         }
     }
 
-    /// Updates various characteristics of a placeholder.
+    /// Updates various characteristics of a placeholder, returning the updated USN.
+    ///
+    /// The update only succeeds if the passed USN matches the current USN of the placeholder.
     ///
     /// Restrictions:
     /// * The file or directory must be the sync root directory itself, or a descendant directory.
@@ -103,17 +118,43 @@ pub trait FileExt: AsRawHandle {
     ///     * [CloudErrorKind::NotInSync][crate::CloudErrorKind::NotInSync]
     /// * The handle must have write access.
     ///     * [CloudErrorKind::AccessDenied][crate::CloudErrorKind::AccessDenied]
+    /// * Each range passed to [UpdateOptions::dehydrate_ranges][UpdateOptions::dehydrate_ranges]
+    ///   must start at a multiple of [transfer_alignment][crate::platform::transfer_alignment],
+    ///   must not be reversed, and must not end past `i64::MAX`.
+    ///     * `E_INVALIDARG`
     // TODO: this could be split into multiple functions to make common patterns easier
-    fn update(&self, usn: Usn, mut options: UpdateOptions) -> core::Result<Usn> {
+    fn update(&self, usn: Usn, options: UpdateOptions) -> core::Result<Usn> {
+        let alignment = platform::transfer_alignment() as u64;
+        let mut dehydrate_range = options
+            .dehydrate_range
+            .iter()
+            .map(|range| {
+                if !range.start.is_multiple_of(alignment) {
+                    return Err(core::Error::new(
+                        E_INVALIDARG,
+                        format!(
+                            "the start of the dehydrate range must be aligned to {alignment}, got {}",
+                            range.start
+                        )
+                        .into(),
+                    ));
+                }
+                FileRange::new(range.clone()).map(CF_FILE_RANGE::from)
+            })
+            .collect::<core::Result<Vec<_>>>()?;
+
         let mut usn = usn as i64;
         unsafe {
             CfUpdatePlaceholder(
                 HANDLE(self.as_raw_handle() as isize),
-                options.metadata.map_or(ptr::null(), |x| &x.0 as *const _),
+                options
+                    .metadata
+                    .as_ref()
+                    .map_or(ptr::null(), |x| &x.0 as *const _),
                 options.blob.map_or(ptr::null(), |x| x.as_ptr() as *const _),
                 options.blob.map_or(0, |x| x.len() as u32),
-                options.dehydrate_range.as_mut_ptr(),
-                options.dehydrate_range.len() as u32,
+                dehydrate_range.as_mut_ptr(),
+                dehydrate_range.len() as u32,
                 options.flags,
                 &mut usn as *mut _,
                 ptr::null_mut(),
@@ -123,29 +164,25 @@ pub trait FileExt: AsRawHandle {
     }
 
     /// Hydrates a placeholder file.
+    ///
+    /// This is an explicit hydration, so it is not blocked by
+    /// [Session::block_implicit_hydration][crate::Session::block_implicit_hydration].
     // TODO: doc restrictions. I believe the remarks are wrong in that this call requires both read
     // and write access? https://docs.microsoft.com/en-us/windows/win32/api/cfapi/nf-cfapi-cfhydrateplaceholder#remarks
     fn hydrate<T: RangeBounds<u64>>(&self, range: T) -> core::Result<()> {
+        let range = FileRange::from_bounds(range)?;
         unsafe {
             CfHydratePlaceholder(
                 HANDLE(self.as_raw_handle() as isize),
-                match range.start_bound() {
-                    Bound::Included(x) => *x as i64,
-                    Bound::Excluded(x) => x.saturating_add(1) as i64,
-                    Bound::Unbounded => 0,
-                },
-                match range.end_bound() {
-                    Bound::Included(x) => *x as i64,
-                    Bound::Excluded(x) => x.saturating_sub(1) as i64,
-                    Bound::Unbounded => -1,
-                },
+                range.offset,
+                range.length,
                 CloudFilters::CF_HYDRATE_FLAG_NONE,
                 ptr::null_mut(),
             )
         }
     }
 
-    /// Dehydrates a placeholder file.
+    /// Dehydrates a placeholder file on behalf of the logged-in user.
     fn dehydrate<T: RangeBounds<u64>>(&self, range: T) -> core::Result<()> {
         dehydrate(self.as_raw_handle(), range, false)
     }
@@ -174,15 +211,89 @@ pub trait FileExt: AsRawHandle {
         .map(|_| length)
     }
 
+    /// Streams the data of a placeholder that has not yet been synced to the cloud into `out`,
+    /// returning the number of bytes written.
+    ///
+    /// The data is read from each [ReadType::Modified][crate::ext::ReadType::Modified] range in
+    /// order and concatenated. This is useful for uploading local changes after a user edit, e.g.
+    /// from [SyncFilter::closed][crate::SyncFilter::closed]. The handle must have read access.
+    fn read_modified_to<W: Write>(&self, mut out: W) -> core::Result<u64> {
+        // the handle is borrowed, it must not be closed when the file is dropped
+        let file = ManuallyDrop::new(unsafe { File::from_raw_handle(self.as_raw_handle()) });
+        let mut buffer = vec![0; MODIFIED_CHUNK_SIZE];
+        let mut written = 0;
+
+        for range in file_ranges(self.as_raw_handle(), ReadType::Modified)? {
+            let mut position = range.StartingOffset as u64;
+            let end = position + range.Length as u64;
+
+            while position < end {
+                let len = ((end - position) as usize).min(buffer.len());
+                let read = file
+                    .seek_read(&mut buffer[..len], position)
+                    .map_err(io_to_core)?;
+                if read == 0 {
+                    break;
+                }
+
+                out.write_all(&buffer[..read]).map_err(io_to_core)?;
+                position += read as u64;
+                written += read as u64;
+            }
+        }
+
+        Ok(written)
+    }
+
+    /// Reads the data of a placeholder within `range` that is of the given
+    /// [ReadType][crate::ext::ReadType], concatenated in order.
+    ///
+    /// Every matching range is read in full, stopping early at the end of the file. Only data on
+    /// the disk is read, so the [SyncFilter][crate::SyncFilter] is never invoked. The handle must
+    /// have read access.
+    fn read_all(&self, read_type: ReadType, range: Range<u64>) -> core::Result<Vec<u8>> {
+        // the handle is borrowed, it must not be closed when the file is dropped
+        let file = ManuallyDrop::new(unsafe { File::from_raw_handle(self.as_raw_handle()) });
+        let mut data = Vec::new();
+
+        for file_range in file_ranges(self.as_raw_handle(), read_type)? {
+            let mut position = (file_range.StartingOffset as u64).max(range.start);
+            let end = ((file_range.StartingOffset + file_range.Length) as u64).min(range.end);
+
+            while position < end {
+                let offset = data.len();
+                data.resize(offset + (end - position) as usize, 0);
+                let read = file
+                    .seek_read(&mut data[offset..], position)
+                    .map_err(io_to_core)?;
+                data.truncate(offset + read);
+
+                // the end of the file has been reached
+                if read == 0 {
+                    return Ok(data);
+                }
+                position += read as u64;
+            }
+        }
+
+        Ok(data)
+    }
+
     /// Gets various characteristics of a placeholder.
+    ///
+    /// The buffer is sized for the largest possible blob, use
+    /// [FileExt::placeholder_info_unchecked][crate::ext::FileExt::placeholder_info_unchecked] if
+    /// the size of the blob is known.
     fn placeholder_info(&self) -> core::Result<PlaceholderInfo> {
-        // TODO: same as below except finds the size after 2 calls of CfGetPlaceholderInfo
-        todo!()
+        self.placeholder_info_unchecked(
+            CloudFilters::CF_PLACEHOLDER_MAX_FILE_IDENTITY_LENGTH as usize,
+        )
     }
 
     /// Gets various characteristics of a placeholder using the passed blob size.
     fn placeholder_info_unchecked(&self, blob_size: usize) -> core::Result<PlaceholderInfo> {
         let mut data = vec![0; mem::size_of::<CF_PLACEHOLDER_STANDARD_INFO>() + blob_size];
+        let mut length = 0u32;
 
         unsafe {
             CfGetPlaceholderInfo(
@@ -190,13 +301,15 @@ pub trait FileExt: AsRawHandle {
                 CloudFilters::CF_PLACEHOLDER_INFO_STANDARD,
                 data.as_mut_ptr() as *mut _,
                 data.len() as u32,
-                ptr::null_mut(),
+                &mut length as *mut _,
             )?;
         }
+        // only keep the blob that was written, truncating does not reallocate
+        data.truncate(length as usize);
 
         Ok(PlaceholderInfo {
             info: &unsafe {
-                data[..=mem::size_of::<CF_PLACEHOLDER_STANDARD_INFO>()]
+                data[..mem::size_of::<CF_PLACEHOLDER_STANDARD_INFO>()]
                     .align_to::<CF_PLACEHOLDER_STANDARD_INFO>()
             }
             .1[0] as *const _,
@@ -253,15 +366,47 @@ pub trait FileExt: AsRawHandle {
         mark_sync_state(self.as_raw_handle(), false, usn)
     }
 
+    /// Gets the correlation vector attached to the placeholder.
+    fn correlation_vector(&self) -> core::Result<CorrelationVector> {
+        unsafe { CfGetCorrelationVector(HANDLE(self.as_raw_handle() as isize)) }
+            .map(CorrelationVector)
+    }
+
+    /// Attaches a correlation vector to the placeholder, which the operating system uses to trace
+    /// subsequent operations on it.
+    fn set_correlation_vector(&self, vector: &CorrelationVector) -> core::Result<()> {
+        unsafe {
+            CfSetCorrelationVector(HANDLE(self.as_raw_handle() as isize), &vector.0 as *const _)
+        }
+    }
+
     /// Returns whether or not the handle is a valid placeholder.
     fn is_placeholder(&self) -> core::Result<bool> {
-        self.placeholder_state().map(|state| state.is_some())
+        self.placeholder_state()
+            .map(|state| matches!(state, Some(state) if state.is_placeholder()))
     }
 
     /// Gets various characteristics of the sync root.
+    ///
+    /// The size of the blob is queried first, so the buffer is sized exactly for it.
     fn sync_root_info(&self) -> core::Result<SyncRootInfo> {
-        // TODO: this except finds the size after 2 calls of CfGetSyncRootInfoByHandle
-        todo!()
+        // the fixed part of the info is filled even if the blob does not fit
+        let mut info = CF_SYNC_ROOT_STANDARD_INFO::default();
+        match unsafe {
+            CfGetSyncRootInfoByHandle(
+                HANDLE(self.as_raw_handle() as isize),
+                CF_SYNC_ROOT_INFO_STANDARD,
+                &mut info as *mut _ as *mut _,
+                mem::size_of::<CF_SYNC_ROOT_STANDARD_INFO>() as u32,
+                ptr::null_mut(),
+            )
+        } {
+            Ok(_) => {}
+            Err(err) if err.win32_error() == Some(ERROR_MORE_DATA) => {}
+            Err(err) => return Err(err),
+        }
+
+        unsafe { self.sync_root_info_unchecked(info.SyncRootIdentityLength as usize) }
     }
 
     #[allow(clippy::missing_safety_doc)]
@@ -296,6 +441,40 @@ pub trait FileExt: AsRawHandle {
     }
 }
 
+/// Gets all of the ranges of the specified type within the placeholder.
+pub(crate) fn file_ranges(
+    handle: RawHandle,
+    read_type: ReadType,
+) -> core::Result<Vec<CF_FILE_RANGE>> {
+    let mut ranges = vec![CF_FILE_RANGE::default(); 16];
+    loop {
+        let mut length = 0u32;
+        let result = unsafe {
+            CfGetPlaceholderRangeInfo(
+                HANDLE(handle as isize),
+                read_type.into(),
+                0,
+                // the rest of the file
+                -1,
+                ranges.as_mut_ptr() as *mut _,
+                (ranges.len() * mem::size_of::<CF_FILE_RANGE>()) as u32,
+                &mut length as *mut _,
+            )
+        };
+
+        match result {
+            Ok(_) => {
+                ranges.truncate(length as usize / mem::size_of::<CF_FILE_RANGE>());
+                return Ok(ranges);
+            }
+            Err(err) if err.win32_error() == Some(ERROR_MORE_DATA) => {
+                ranges.resize(ranges.len() * 2, CF_FILE_RANGE::default());
+            }
+            Err(err) => return Err(err),
+        }
+    }
+}
+
 fn mark_sync_state(handle: RawHandle, sync: bool, usn: Usn) -> core::Result<Usn> {
     // TODO: docs say the usn NEEDS to be a null pointer? Why? Is it not supported?
     // https://docs.microsoft.com/en-us/windows/win32/api/cfapi/nf-cfapi-cfsetinsyncstate
@@ -322,30 +501,27 @@ fn dehydrate<T: RangeBounds<u64>>(
     range: T,
     background: bool,
 ) -> core::Result<()> {
+    // an unbounded end dehydrates the rest of the file, as documented in CfDehydratePlaceholder
+    let range = FileRange::from_bounds(range)?;
     unsafe {
         CfDehydratePlaceholder(
             HANDLE(handle as isize),
-            match range.start_bound() {
-                Bound::Included(x) => *x as i64,
-                Bound::Excluded(x) => x.saturating_add(1) as i64,
-                Bound::Unbounded => 0,
-            },
-            match range.end_bound() {
-                Bound::Included(x) => *x as i64,
-                Bound::Excluded(x) => x.saturating_sub(1) as i64,
-                // This behavior is documented in CfDehydratePlaceholder
-                Bound::Unbounded => -1,
-            },
-            if background {
-                CloudFilters::CF_DEHYDRATE_FLAG_NONE
-            } else {
-                CloudFilters::CF_DEHYDRATE_FLAG_BACKGROUND
-            },
+            range.offset,
+            range.length,
+            dehydrate_flags(background),
             ptr::null_mut(),
         )
     }
 }
 
+fn dehydrate_flags(background: bool) -> CF_DEHYDRATE_FLAGS {
+    if background {
+        CloudFilters::CF_DEHYDRATE_FLAG_BACKGROUND
+    } else {
+        CloudFilters::CF_DEHYDRATE_FLAG_NONE
+    }
+}
+
 impl FileExt for File {}
 
 /// The type of data to read from a placeholder.
@@ -356,6 +532,9 @@ pub enum ReadType {
     /// Data that has been synced to the cloud.
     Validated,
     /// Data that has not synced to the cloud.
+    ///
+    /// A range becomes modified when it is written to locally and stops being modified once the
+    /// placeholder is marked as in sync, e.g. through [FileExt::mark_sync][crate::ext::FileExt::mark_sync].
     Modified,
 }
 
@@ -369,7 +548,14 @@ impl From<ReadType> for CF_PLACEHOLDER_RANGE_INFO_CLASS {
     }
 }
 
-/// Information about a sync root.
+/// Information about a sync root, read from the Cloud Filter API.
+///
+/// Strings are borrowed from the buffer filled by the operating system, so they are returned as
+/// [U16CStr][widestring::U16CStr] rather than converted. The registration as reported by the
+/// Windows Runtime is available from [PathExt::sync_root_info][crate::ext::PathExt::sync_root_info].
+///
+/// The display name of the sync root is not included, read
+/// [PathExt::sync_root_display_name][crate::ext::PathExt::sync_root_display_name] instead.
 #[derive(Debug)]
 pub struct SyncRootInfo {
     data: Vec<u8>,
@@ -425,12 +611,14 @@ impl SyncRootInfo {
 
     /// The register blob associated with the sync root.
     pub fn blob(&self) -> &[u8] {
-        &self.data[(mem::size_of::<CF_SYNC_ROOT_STANDARD_INFO>() + 1)..]
+        let offset = offset_of!(CF_SYNC_ROOT_STANDARD_INFO, SyncRootIdentity);
+        let length = unsafe { &*self.info }.SyncRootIdentityLength as usize;
+        &self.data[offset..][..length]
     }
 }
 
 /// Sync provider status.
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ProviderStatus {
     /// The sync provider is disconnected.
     Disconnected,
@@ -462,7 +650,7 @@ impl From<CF_SYNC_PROVIDER_STATUS> for ProviderStatus {
             CloudFilters::CF_PROVIDER_STATUS_DISCONNECTED => Self::Disconnected,
             CloudFilters::CF_PROVIDER_STATUS_IDLE => Self::Idle,
             CloudFilters::CF_PROVIDER_STATUS_POPULATE_NAMESPACE => Self::PopulateNamespace,
-            CloudFilters::CF_PROVIDER_STATUS_POPULATE_METADATA => Self::PopulateContent,
+            CloudFilters::CF_PROVIDER_STATUS_POPULATE_METADATA => Self::PopulateMetadata,
             CloudFilters::CF_PROVIDER_STATUS_POPULATE_CONTENT => Self::PopulateContent,
             CloudFilters::CF_PROVIDER_STATUS_SYNC_INCREMENTAL => Self::SyncIncremental,
             CloudFilters::CF_PROVIDER_STATUS_SYNC_FULL => Self::SyncFull,
@@ -661,7 +849,7 @@ impl Default for ConvertOptions<'_> {
 #[derive(Debug, Clone)]
 pub struct UpdateOptions<'a> {
     metadata: Option<Metadata>,
-    dehydrate_range: Vec<CF_FILE_RANGE>,
+    dehydrate_range: Vec<Range<u64>>,
     flags: CF_UPDATE_FLAGS,
     blob: Option<&'a [u8]>,
 }
@@ -672,12 +860,22 @@ impl<'a> UpdateOptions<'a> {
         self
     }
 
-    // TODO: user should be able to specify an array of RangeBounds
-    pub fn dehydrate_range(mut self, range: Range<u64>) -> Self {
-        self.dehydrate_range.push(CF_FILE_RANGE {
-            StartingOffset: range.start as i64,
-            Length: range.end as i64,
-        });
+    /// Dehydrates the specified range of the placeholder.
+    ///
+    /// The start of the range must be aligned to
+    /// [transfer_alignment][crate::platform::transfer_alignment], otherwise
+    /// [FileExt::update][crate::ext::FileExt::update] returns `E_INVALIDARG`.
+    pub fn dehydrate_range(self, range: Range<u64>) -> Self {
+        self.dehydrate_ranges(iter::once(range))
+    }
+
+    /// Dehydrates each of the specified ranges of the placeholder in a single update.
+    ///
+    /// The start of each range must be aligned to
+    /// [transfer_alignment][crate::platform::transfer_alignment], otherwise
+    /// [FileExt::update][crate::ext::FileExt::update] returns `E_INVALIDARG`.
+    pub fn dehydrate_ranges(mut self, ranges: impl IntoIterator<Item = Range<u64>>) -> Self {
+        self.dehydrate_range.extend(ranges);
         self
     }
 
@@ -703,6 +901,27 @@ impl<'a> UpdateOptions<'a> {
         self
     }
 
+    /// Blocks this placeholder from being dehydrated.
+    ///
+    /// Any following dehydration requests will fail with
+    /// [CloudErrorKind::DehydrationDisallowed][crate::CloudErrorKind::DehydrationDisallowed]. This
+    /// flag does not work on directories.
+    pub fn block_dehydration(mut self) -> Self {
+        self.flags |= CloudFilters::CF_UPDATE_FLAG_ALWAYS_FULL;
+        self
+    }
+
+    /// Allows this placeholder to be dehydrated again after it was blocked by
+    /// [UpdateOptions::block_dehydration][UpdateOptions::block_dehydration],
+    /// [ConvertOptions::block_dehydration][ConvertOptions::block_dehydration], or
+    /// [PlaceholderFile::block_dehydration][crate::PlaceholderFile::block_dehydration].
+    ///
+    /// This flag does not work on directories.
+    pub fn allow_dehydration(mut self) -> Self {
+        self.flags |= CloudFilters::CF_UPDATE_FLAG_ALLOW_PARTIAL;
+        self
+    }
+
     pub fn remove_blob(mut self) -> Self {
         self.flags |= CloudFilters::CF_UPDATE_FLAG_REMOVE_FILE_IDENTITY;
         self
@@ -713,7 +932,13 @@ impl<'a> UpdateOptions<'a> {
         self
     }
 
-    // TODO: what does this do?
+    /// Removes all of the properties stored with the placeholder.
+    ///
+    /// Properties are opaque blobs attached by the platform (e.g. the shell), each stored with a
+    /// version and checksum that are verified when read. The Cloud Filter API does not offer a way
+    /// for sync providers to set them, errors such as
+    /// [CloudErrorKind::PropertyBlobChecksumMismatch][crate::CloudErrorKind::PropertyBlobChecksumMismatch]
+    /// could be recovered from by removing them.
     pub fn remove_properties(mut self) -> Self {
         self.flags |= CloudFilters::CF_UPDATE_FLAG_REMOVE_PROPERTY;
         self
@@ -749,33 +974,60 @@ impl Default for UpdateOptions<'_> {
     }
 }
 
-// TODO: I don't think this is an enum
+/// The state of a placeholder.
+///
+/// A placeholder could be in multiple states at once, e.g. a placeholder could be both in sync and
+/// partially on disk.
 #[derive(Debug, Clone, Copy)]
-pub enum PlaceholderState {
-    Placeholder,
-    SyncRoot,
-    EssentialPropPresent,
-    InSync,
-    StatePartial,
-    PartiallyOnDisk,
-}
+pub struct PlaceholderState(CF_PLACEHOLDER_STATE);
 
 impl PlaceholderState {
     fn try_from_win32(value: CF_PLACEHOLDER_STATE) -> core::Result<Option<PlaceholderState>> {
         match value {
             CloudFilters::CF_PLACEHOLDER_STATE_NO_STATES => Ok(None),
-            CloudFilters::CF_PLACEHOLDER_STATE_PLACEHOLDER => Ok(Some(Self::Placeholder)),
-            CloudFilters::CF_PLACEHOLDER_STATE_SYNC_ROOT => Ok(Some(Self::SyncRoot)),
-            CloudFilters::CF_PLACEHOLDER_STATE_ESSENTIAL_PROP_PRESENT => {
-                Ok(Some(Self::EssentialPropPresent))
-            }
-            CloudFilters::CF_PLACEHOLDER_STATE_IN_SYNC => Ok(Some(Self::InSync)),
-            CloudFilters::CF_PLACEHOLDER_STATE_PARTIAL => Ok(Some(Self::StatePartial)),
-            CloudFilters::CF_PLACEHOLDER_STATE_PARTIALLY_ON_DISK => Ok(Some(Self::PartiallyOnDisk)),
             CloudFilters::CF_PLACEHOLDER_STATE_INVALID => Err(core::Error::from_win32()),
-            _ => unreachable!(),
+            state => Ok(Some(Self(state))),
         }
     }
+
+    fn contains(&self, state: CF_PLACEHOLDER_STATE) -> bool {
+        (self.0 & state).0 != 0
+    }
+
+    /// Whether or not the file/directory is a placeholder.
+    pub fn is_placeholder(&self) -> bool {
+        self.contains(CloudFilters::CF_PLACEHOLDER_STATE_PLACEHOLDER)
+    }
+
+    /// Whether or not the directory is a sync root.
+    pub fn is_sync_root(&self) -> bool {
+        self.contains(CloudFilters::CF_PLACEHOLDER_STATE_SYNC_ROOT)
+    }
+
+    /// Whether or not the essential properties of the placeholder are present.
+    pub fn essential_prop_present(&self) -> bool {
+        self.contains(CloudFilters::CF_PLACEHOLDER_STATE_ESSENTIAL_PROP_PRESENT)
+    }
+
+    /// Whether or not the placeholder is in sync.
+    ///
+    /// Read [PlaceholderInfo::is_synced][crate::ext::PlaceholderInfo::is_synced] for what marks a
+    /// placeholder as not in sync.
+    pub fn is_synced(&self) -> bool {
+        self.contains(CloudFilters::CF_PLACEHOLDER_STATE_IN_SYNC)
+    }
+
+    /// Whether or not the placeholder is partial, meaning its content is not fully present on the
+    /// disk. For directories, this means its children have not been fully populated.
+    pub fn is_partial(&self) -> bool {
+        self.contains(CloudFilters::CF_PLACEHOLDER_STATE_PARTIAL)
+    }
+
+    /// Whether or not some, but not all, of the content of the placeholder is present on the
+    /// disk.
+    pub fn is_partially_on_disk(&self) -> bool {
+        self.contains(CloudFilters::CF_PLACEHOLDER_STATE_PARTIALLY_ON_DISK)
+    }
 }
 
 #[derive(Debug)]
@@ -785,24 +1037,61 @@ pub struct PlaceholderInfo {
 }
 
 impl PlaceholderInfo {
+    /// The number of bytes of the placeholder that are present on the disk, i.e. hydrated.
     pub fn on_disk_data_size(&self) -> u64 {
         unsafe { &*self.info }.OnDiskDataSize as u64
     }
 
+    /// The number of bytes present on the disk that are in sync with the cloud.
+    ///
+    /// This is a subset of [PlaceholderInfo::on_disk_data_size][crate::ext::PlaceholderInfo::on_disk_data_size].
     pub fn validated_data_size(&self) -> u64 {
         unsafe { &*self.info }.ValidatedDataSize as u64
     }
+
+    /// The number of bytes present on the disk that were modified locally and are not yet in sync
+    /// with the cloud.
+    ///
+    /// This is a subset of [PlaceholderInfo::on_disk_data_size][crate::ext::PlaceholderInfo::on_disk_data_size].
     pub fn modified_data_size(&self) -> u64 {
         unsafe { &*self.info }.ModifiedDataSize as u64
     }
+
+    /// The total size of the properties stored with the placeholder, read
+    /// [UpdateOptions::remove_properties][crate::ext::UpdateOptions::remove_properties] for more
+    /// information.
+    ///
+    /// The properties themselves, and the format they are stored in, are private to the
+    /// operating system and can not be read or written through the Cloud Filter API. Custom data
+    /// should be stored in the placeholder's blob instead.
     pub fn properties_size(&self) -> u64 {
         unsafe { &*self.info }.PropertiesSize as u64
     }
 
+    /// The hydration progress of the placeholder as `(present, total)` bytes, useful for
+    /// displaying a download indicator.
+    ///
+    /// The total is the logical size of the file, which is not stored within the placeholder
+    /// info and must be passed in, e.g. from [Metadata::len][std::fs::Metadata::len]. `None` is
+    /// returned for empty files and directories.
+    pub fn hydration_progress(&self, file_size: u64) -> Option<(u64, u64)> {
+        match file_size {
+            0 => None,
+            total => Some((self.on_disk_data_size().min(total), total)),
+        }
+    }
+
     pub fn pin_state(&self) -> PinState {
         unsafe { &*self.info }.PinState.into()
     }
 
+    /// Whether or not the placeholder is in sync.
+    ///
+    /// The operating system only tracks a single in-sync state per placeholder, it does not record
+    /// which attribute caused it to fall out of sync. A change to any attribute selected by
+    /// [SupportedAttributes][crate::SupportedAttributes] on registration marks the whole
+    /// placeholder as not in sync, so the provider must compare the attributes against the remote
+    /// to determine what changed.
     pub fn is_synced(&self) -> bool {
         unsafe { &*self.info }.InSyncState == CloudFilters::CF_IN_SYNC_STATE_IN_SYNC
     }
@@ -819,3 +1108,81 @@ impl PlaceholderInfo {
         &self.data[mem::size_of::<CF_PLACEHOLDER_STANDARD_INFO>()..]
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs};
+
+    use widestring::U16String;
+
+    use super::*;
+    use crate::{ext::PathExt, walk, Registration, SecurityId, SyncRootIdBuilder};
+
+    #[test]
+    fn provider_status_round_trip() {
+        let statuses = [
+            ProviderStatus::Disconnected,
+            ProviderStatus::Idle,
+            ProviderStatus::PopulateNamespace,
+            ProviderStatus::PopulateMetadata,
+            ProviderStatus::PopulateContent,
+            ProviderStatus::SyncIncremental,
+            ProviderStatus::SyncFull,
+            ProviderStatus::ConnectivityLost,
+            ProviderStatus::Terminated,
+            ProviderStatus::Error,
+        ];
+        for status in statuses {
+            assert_eq!(
+                ProviderStatus::from(CF_SYNC_PROVIDER_STATUS::from(status)),
+                status
+            );
+        }
+    }
+
+    #[test]
+    fn dehydrate_flags_by_method() {
+        // FileExt::dehydrate
+        assert_eq!(dehydrate_flags(false), CloudFilters::CF_DEHYDRATE_FLAG_NONE);
+        // FileExt::background_dehydrate
+        assert_eq!(
+            dehydrate_flags(true),
+            CloudFilters::CF_DEHYDRATE_FLAG_BACKGROUND
+        );
+    }
+
+    #[test]
+    fn sync_root_info_matches_path_ext() {
+        let path = env::temp_dir().join("wincs-sync-root-info");
+        fs::create_dir_all(&path).unwrap();
+
+        let sync_root_id = SyncRootIdBuilder::new(U16String::from_str("wincs-test"))
+            .user_security_id(SecurityId::current_user().unwrap())
+            .account_name(U16String::from_str("sync-root-info"))
+            .build();
+        let version = U16String::from_str("1.2.3");
+        let _registration = Registration::from_sync_root_id(&sync_root_id)
+            .version(&version)
+            .blob(b"sync root blob")
+            .register_scoped(&path)
+            .unwrap();
+
+        let info = walk::open_attributes(&path)
+            .unwrap()
+            .sync_root_info()
+            .unwrap();
+        let winrt_info = path.sync_root_info().unwrap();
+
+        assert_eq!(
+            info.version().to_string_lossy(),
+            winrt_info.Version().unwrap().to_string()
+        );
+        assert_eq!(info.version().to_string_lossy(), "1.2.3");
+        let id = winrt_info.Id().unwrap().to_string();
+        assert_eq!(
+            Some(info.provider_name().to_string_lossy().as_str()),
+            id.split('!').next()
+        );
+        assert_eq!(info.blob(), b"sync root blob");
+    }
+}
@@ -0,0 +1,155 @@
+use std::{
+    ffi::{OsStr, OsString},
+    path::Path,
+    ptr,
+};
+
+use widestring::{U16CStr, U16CString};
+use windows::{
+    core::{self, HRESULT, PWSTR},
+    Win32::{
+        Foundation::{ERROR_MORE_DATA, E_INVALIDARG, WIN32_ERROR},
+        System::RestartManager::{
+            RmEndSession, RmGetList, RmRegisterResources, RmStartSession, CCH_RM_SESSION_KEY,
+            RM_PROCESS_INFO,
+        },
+    },
+};
+
+/// A process that holds a file open, returned by
+/// [find_handle_holders][crate::ext::find_handle_holders].
+#[derive(Debug, Clone)]
+pub struct HandleHolder {
+    id: u32,
+    name: OsString,
+    session_id: u32,
+}
+
+impl HandleHolder {
+    /// The ID of the process.
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+
+    /// The name of the application, suitable to be shown to the user, e.g. "file is open in
+    /// Notepad".
+    pub fn name(&self) -> &OsStr {
+        &self.name
+    }
+
+    /// The ID of the session where the process resides.
+    pub fn session_id(&self) -> u32 {
+        self.session_id
+    }
+}
+
+/// Finds the processes that hold the file at the given path open, e.g. when an operation failed
+/// with [CloudErrorKind::InUse][crate::CloudErrorKind::InUse].
+///
+/// The processes are queried through the
+/// [Restart Manager](https://docs.microsoft.com/en-us/windows/win32/rstmgr/restart-manager-portal),
+/// which does not require any privileges. However, processes running as another user or at a
+/// higher integrity level are only listed if the calling process is elevated.
+pub fn find_handle_holders(path: &Path) -> core::Result<Vec<HandleHolder>> {
+    let path = U16CString::from_os_str(path).map_err(|_| {
+        core::Error::new(
+            E_INVALIDARG,
+            "the path must not contain a null character".into(),
+        )
+    })?;
+
+    let mut session = 0;
+    let mut key = [0u16; CCH_RM_SESSION_KEY as usize + 1];
+    win32(unsafe { RmStartSession(&mut session, 0, PWSTR(key.as_mut_ptr())) })?;
+
+    let result = list(session, &path);
+    // the list is more useful than a failure to clean up
+    unsafe { RmEndSession(session) };
+
+    result
+}
+
+fn list(session: u32, path: &U16CStr) -> core::Result<Vec<HandleHolder>> {
+    let files = [PWSTR(path.as_ptr() as *mut _)];
+    win32(unsafe {
+        RmRegisterResources(
+            session,
+            files.len() as u32,
+            files.as_ptr(),
+            0,
+            ptr::null(),
+            0,
+            ptr::null(),
+        )
+    })?;
+
+    let mut processes = Vec::new();
+    loop {
+        let mut needed = 0;
+        let mut len = processes.len() as u32;
+        let mut reasons = 0;
+        let error = unsafe {
+            RmGetList(
+                session,
+                &mut needed,
+                &mut len,
+                processes.as_mut_ptr(),
+                &mut reasons,
+            )
+        };
+
+        // a process could open the file in between calls, in which case the list is read again
+        if WIN32_ERROR(error) == ERROR_MORE_DATA {
+            processes.resize(needed as usize, RM_PROCESS_INFO::default());
+            continue;
+        }
+
+        win32(error)?;
+        processes.truncate(len as usize);
+        break;
+    }
+
+    Ok(processes
+        .iter()
+        .map(|info| HandleHolder {
+            id: info.Process.dwProcessId,
+            name: U16CStr::from_slice_truncate(&info.strAppName)
+                .map(U16CStr::to_os_string)
+                .unwrap_or_default(),
+            session_id: info.TSSessionId,
+        })
+        .collect())
+}
+
+fn win32(error: u32) -> core::Result<()> {
+    HRESULT::from(WIN32_ERROR(error)).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        env,
+        fs::{self, File},
+        process,
+    };
+
+    use super::*;
+
+    #[test]
+    fn find_handle_holders_rejects_interior_null() {
+        let err = find_handle_holders(Path::new("file\0name")).unwrap_err();
+        assert_eq!(err.code(), E_INVALIDARG);
+    }
+
+    #[test]
+    fn find_handle_holders_lists_current_process() {
+        let path = env::temp_dir().join("wincs-handle-holders.txt");
+        let file = File::create(&path).unwrap();
+
+        let holders = find_handle_holders(&path).unwrap();
+        assert!(holders.iter().any(|holder| holder.id() == process::id()));
+
+        drop(file);
+        fs::remove_file(&path).unwrap();
+    }
+}
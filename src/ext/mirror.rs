@@ -0,0 +1,76 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use windows::core;
+
+use crate::{
+    placeholder_file::{BatchCreate, PlaceholderFile},
+    usn::Usn,
+    utility::io_to_core,
+};
+
+/// Mirrors the directory tree at `source` as placeholders under `dest_root`.
+///
+/// Each placeholder is created with the metadata of its source entry, marked as in sync, and
+/// given the blob returned by `blob_for`, which is passed the path relative to `source`. Entries
+/// that already exist under `dest_root` are skipped, although existing directories are still
+/// descended into. Placeholders in the same directory are created in a single batch.
+///
+/// The returned vector contains the path of each attempted placeholder along with its result. A
+/// directory that fails to be created is not descended into.
+pub fn mirror_as_placeholders(
+    source: &Path,
+    dest_root: &Path,
+    blob_for: impl Fn(&Path) -> Vec<u8>,
+) -> core::Result<Vec<(PathBuf, core::Result<Usn>)>> {
+    let mut results = Vec::new();
+    let mut pending = vec![PathBuf::new()];
+
+    while let Some(relative_dir) = pending.pop() {
+        let mut entries = Vec::new();
+        let mut placeholders = Vec::new();
+
+        for entry in fs::read_dir(source.join(&relative_dir)).map_err(io_to_core)? {
+            let entry = entry.map_err(io_to_core)?;
+            let metadata = entry.metadata().map_err(io_to_core)?;
+            let relative_path = relative_dir.join(entry.file_name());
+            let is_dir = metadata.is_dir();
+
+            if dest_root.join(&relative_path).exists() {
+                if is_dir {
+                    pending.push(relative_path);
+                }
+                continue;
+            }
+
+            let mut placeholder = PlaceholderFile::new(entry.file_name())?
+                .metadata(metadata.into())
+                .mark_sync()
+                .blob(blob_for(&relative_path));
+            // children are mirrored by this function rather than populated on demand
+            if is_dir {
+                placeholder = placeholder.has_no_children();
+            }
+
+            placeholders.push(placeholder);
+            entries.push((relative_path, is_dir));
+        }
+
+        if placeholders.is_empty() {
+            continue;
+        }
+
+        let created = placeholders.create(dest_root.join(&relative_dir))?;
+        for ((relative_path, is_dir), result) in entries.into_iter().zip(created) {
+            if is_dir && result.is_ok() {
+                pending.push(relative_path.clone());
+            }
+
+            results.push((dest_root.join(relative_path), result));
+        }
+    }
+
+    Ok(results)
+}
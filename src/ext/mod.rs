@@ -1,8 +1,17 @@
 mod file;
+mod holders;
+mod mirror;
 mod path;
+mod pin;
+mod scan;
 
+pub(crate) use file::file_ranges;
 pub use file::{
     ConvertOptions, FileExt, PinOptions, PinState, PlaceholderInfo, PlaceholderState,
-    ProviderStatus, SyncRootInfo, UpdateOptions,
+    ProviderStatus, ReadType, SyncRootInfo, UpdateOptions,
 };
+pub use holders::{find_handle_holders, HandleHolder};
+pub use mirror::mirror_as_placeholders;
 pub use path::PathExt;
+pub use pin::set_pin_states;
+pub use scan::find_partial_placeholders;
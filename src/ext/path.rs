@@ -7,9 +7,17 @@ use windows::{
         Provider::{StorageProviderSyncRootInfo, StorageProviderSyncRootManager},
         StorageFolder,
     },
+    Win32::Storage::FileSystem::{
+        self, GetFileAttributesW, FILE_ATTRIBUTE_OFFLINE, FILE_ATTRIBUTE_RECALL_ON_DATA_ACCESS,
+    },
 };
 
-use crate::utility::ToHString;
+use crate::{
+    ext::FileExt,
+    root::SyncRootId,
+    utility::{io_to_core, ToHString},
+    walk,
+};
 
 /// An API extension to [Path][std::path::Path]
 pub trait PathExt
@@ -23,6 +31,13 @@ where
 
     // TODO: This call requires a struct to be made for getters of StorageProviderSyncRootInfo
     /// Information about the sync root that the path is located in.
+    ///
+    /// This is the registration as reported by the Windows Runtime, the same as
+    /// [SyncRootId::info][crate::SyncRootId::info], rather than the
+    /// [SyncRootInfo][crate::ext::SyncRootInfo] read from the Cloud Filter API. Its `Version` is
+    /// the same value as [SyncRootInfo::version][crate::ext::SyncRootInfo::version], while the
+    /// provider name is part of its `Id`, read
+    /// [SyncRootId::try_components][crate::SyncRootId::try_components].
     fn sync_root_info(&self) -> core::Result<StorageProviderSyncRootInfo> {
         StorageProviderSyncRootManager::GetSyncRootInformationForFolder(
             StorageFolder::GetFolderFromPathAsync(
@@ -31,6 +46,154 @@ where
             .get()?,
         )
     }
+
+    /// The display name of the sync root that the path is located in, as shown in the file
+    /// explorer.
+    ///
+    /// The display name is not part of [SyncRootInfo][crate::ext::SyncRootInfo], so it is read
+    /// from the latest registration of the sync root. The path could point to either a file or a
+    /// directory.
+    fn sync_root_display_name(&self) -> core::Result<U16String> {
+        let path = self.as_ref();
+        // only folders could be resolved to a sync root
+        let folder = match path.parent() {
+            Some(parent) if !path.is_dir() => parent,
+            _ => path,
+        };
+
+        let name = SyncRootId::from_path(folder)?
+            .info()?
+            .DisplayNameResource()?;
+        Ok(U16String::from_vec(name.as_wide()))
+    }
+
+    /// Whether or not the file at the path is dehydrated, meaning some or all of its content is
+    /// not present on the disk.
+    ///
+    /// This is a heuristic based on the file attributes, which are read without opening the file,
+    /// making it much cheaper than [FileExt::placeholder_info][crate::ext::FileExt::placeholder_info].
+    /// A partially hydrated file is also considered dehydrated.
+    fn is_dehydrated(&self) -> core::Result<bool> {
+        let attributes = unsafe { GetFileAttributesW(self.as_ref().as_os_str()) };
+        if attributes == FileSystem::INVALID_FILE_ATTRIBUTES {
+            return Err(core::Error::from_win32());
+        }
+
+        Ok(attributes & (FILE_ATTRIBUTE_OFFLINE.0 | FILE_ATTRIBUTE_RECALL_ON_DATA_ACCESS.0) != 0)
+    }
+
+    /// Whether or not the file/directory at the path still needs to be converted to a
+    /// placeholder, which is the case if it is not yet a placeholder or if it is not in sync.
+    ///
+    /// This is useful to skip entries that were already converted when seeding a sync root again,
+    /// e.g. on reconnect. The entry is opened without read access, so it is not hydrated.
+    fn needs_conversion(&self) -> core::Result<bool> {
+        let state = walk::open_attributes(self.as_ref())
+            .map_err(io_to_core)?
+            .placeholder_state()?;
+        Ok(!state.is_some_and(|state| state.is_placeholder() && state.is_synced()))
+    }
+
+    /// The byte slice assigned to the placeholder at the path, equivalent to
+    /// [Request::file_blob][crate::Request::file_blob] outside of callbacks.
+    ///
+    /// The placeholder is opened without read access, so it is not hydrated.
+    fn file_blob(&self) -> core::Result<Vec<u8>> {
+        Ok(walk::open_attributes(self.as_ref())
+            .map_err(io_to_core)?
+            .placeholder_info()?
+            .blob()
+            .to_vec())
+    }
 }
 
 impl<T: AsRef<Path>> PathExt for T {}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs};
+
+    use super::*;
+    use crate::{
+        filter::{info, ticket},
+        Metadata, PlaceholderFile, Registration, Request, SecurityId, Session, SyncFilter,
+        SyncRootIdBuilder,
+    };
+
+    struct DehydrateFilter;
+
+    impl SyncFilter for DehydrateFilter {
+        fn dehydrate(&self, _request: Request, ticket: ticket::Dehydrate, _info: info::Dehydrate) {
+            #[allow(unused_must_use)]
+            {
+                ticket.pass();
+            }
+        }
+    }
+
+    #[test]
+    fn is_dehydrated_after_dehydrating() {
+        let path = env::temp_dir().join("wincs-is-dehydrated");
+        fs::create_dir_all(&path).unwrap();
+
+        let sync_root_id = SyncRootIdBuilder::new(U16String::from_str("wincs-test"))
+            .user_security_id(SecurityId::current_user().unwrap())
+            .account_name(U16String::from_str("is-dehydrated"))
+            .build();
+        let _registration = Registration::from_sync_root_id(&sync_root_id)
+            .register_scoped(&path)
+            .unwrap();
+
+        // deletes are not approved by the filter, so leftovers are removed before connecting
+        let file = path.join("dehydrated.txt");
+        let _ = fs::remove_file(&file);
+        let connection = Session::new()
+            .index(false)
+            .connect(&path, DehydrateFilter)
+            .unwrap();
+
+        let data = vec![1; 4096];
+        PlaceholderFile::new("dehydrated.txt")
+            .unwrap()
+            .metadata(Metadata::file().size(data.len() as u64))
+            .create_present(&path, &data[..])
+            .unwrap();
+        assert!(!file.is_dehydrated().unwrap());
+
+        walk::open_write(&file).unwrap().dehydrate(..).unwrap();
+        assert!(file.is_dehydrated().unwrap());
+
+        connection.disconnect().unwrap();
+        fs::remove_file(&file).unwrap();
+    }
+
+    #[test]
+    fn sync_root_display_name_for_file() {
+        let path = env::temp_dir().join("wincs-display-name");
+        fs::create_dir_all(&path).unwrap();
+
+        let sync_root_id = SyncRootIdBuilder::new(U16String::from_str("wincs-test"))
+            .user_security_id(SecurityId::current_user().unwrap())
+            .account_name(U16String::from_str("display-name"))
+            .build();
+        let display_name = U16String::from_str("wincs display name");
+        let _registration = Registration::from_sync_root_id(&sync_root_id)
+            .display_name(&display_name)
+            .register_scoped(&path)
+            .unwrap();
+
+        let file = path.join("named.txt");
+        let _ = fs::remove_file(&file);
+        PlaceholderFile::new("named.txt")
+            .unwrap()
+            .metadata(Metadata::file())
+            .mark_sync()
+            .create(&path)
+            .unwrap();
+
+        assert_eq!(file.sync_root_display_name().unwrap(), display_name);
+        assert_eq!(path.sync_root_display_name().unwrap(), display_name);
+
+        fs::remove_file(&file).unwrap();
+    }
+}
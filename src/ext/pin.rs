@@ -0,0 +1,32 @@
+use std::path::PathBuf;
+
+use windows::core;
+
+use crate::{
+    ext::{FileExt, PinOptions, PinState},
+    utility::io_to_core,
+    walk,
+};
+
+/// Sets the pin state of each placeholder, e.g. when the user selects "Always keep on this device"
+/// for multiple items, returning the result for each path.
+///
+/// Each placeholder is opened and pinned individually, so the operation is not atomic and a
+/// failure does not prevent the remaining placeholders from being pinned. Whether the pin state
+/// applies to the descendants of a directory is controlled by the
+/// [PinOptions][crate::ext::PinOptions]. The placeholders are opened without read access, so
+/// none are hydrated.
+pub fn set_pin_states(
+    items: &[(PathBuf, PinState)],
+    options: PinOptions,
+) -> Vec<(PathBuf, core::Result<()>)> {
+    items
+        .iter()
+        .map(|(path, state)| {
+            let result = walk::open_write(path)
+                .map_err(io_to_core)
+                .and_then(|file| file.set_pin_state(*state, options));
+            (path.clone(), result)
+        })
+        .collect()
+}
@@ -0,0 +1,43 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use windows::core;
+
+use crate::{
+    ext::FileExt,
+    utility::io_to_core,
+    walk::{self, Walk},
+};
+
+/// Finds every placeholder file beneath `root` that is partially on disk.
+///
+/// A placeholder is left in this state when a hydration is interrupted, e.g. when the provider
+/// crashes mid-download. This could be called on startup to find the placeholders that should be
+/// hydrated again through [FileExt::hydrate][crate::ext::FileExt::hydrate].
+///
+/// Every entry beneath `root` is opened to read its state, although without read access, so no
+/// placeholder is hydrated. Entries that could not be read, e.g. because they were removed during
+/// the scan, are skipped. An error is only returned if `root` itself could not be read.
+pub fn find_partial_placeholders(root: &Path) -> core::Result<Vec<PathBuf>> {
+    // entries that fail are skipped below, so an unreadable root would otherwise look empty
+    fs::read_dir(root).map_err(io_to_core)?;
+
+    let partial = Walk::new(root)
+        .filter_map(Result::ok)
+        .filter(|entry| entry.file_type().is_ok_and(|file_type| !file_type.is_dir()))
+        .map(|entry| entry.path())
+        .filter(|path| {
+            walk::open_attributes(path)
+                .map_err(io_to_core)
+                .and_then(|file| file.placeholder_state())
+                .is_ok_and(|state| {
+                    state
+                        .is_some_and(|state| state.is_placeholder() && state.is_partially_on_disk())
+                })
+        })
+        .collect();
+
+    Ok(partial)
+}
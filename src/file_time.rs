@@ -0,0 +1,141 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// A timestamp counted in 100-nanosecond intervals since January 1, 1601 (UTC), the format used
+/// by the operating system for file times.
+///
+/// Timestamps are limited to the range of a signed 64-bit integer, as that is how they are
+/// stored within a placeholder's [Metadata][crate::Metadata].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct FileTime(pub(crate) u64);
+
+impl FileTime {
+    // the amount of 100-nanosecond intervals in a second
+    const TICKS_PER_SECOND: u64 = 10_000_000;
+    // the amount of seconds between January 1, 1601 and January 1, 1970
+    const UNIX_EPOCH_SECONDS: u64 = 11_644_473_600;
+    const MAX_TICKS: u64 = i64::MAX as u64;
+
+    /// Creates a [FileTime][crate::FileTime] from a raw count of 100-nanosecond intervals since
+    /// January 1, 1601.
+    pub fn from_ticks(ticks: u64) -> Option<Self> {
+        (ticks <= Self::MAX_TICKS).then_some(Self(ticks))
+    }
+
+    /// The raw count of 100-nanosecond intervals since January 1, 1601.
+    pub fn ticks(&self) -> u64 {
+        self.0
+    }
+
+    /// Creates a [FileTime][crate::FileTime] from a [SystemTime][std::time::SystemTime].
+    ///
+    /// Returns `None` if the time is before January 1, 1601 or too far in the future to be
+    /// represented. Precision finer than 100 nanoseconds is truncated.
+    pub fn from_system_time(time: SystemTime) -> Option<Self> {
+        let epoch = Duration::from_secs(Self::UNIX_EPOCH_SECONDS);
+        let since_1601 = match time.duration_since(UNIX_EPOCH) {
+            Ok(duration) => duration.checked_add(epoch)?,
+            Err(err) => epoch.checked_sub(err.duration())?,
+        };
+
+        u64::try_from(since_1601.as_nanos() / 100)
+            .ok()
+            .and_then(Self::from_ticks)
+    }
+
+    /// Converts the [FileTime][crate::FileTime] to a [SystemTime][std::time::SystemTime].
+    ///
+    /// Returns `None` if the time cannot be represented by the platform.
+    pub fn to_system_time(&self) -> Option<SystemTime> {
+        let since_1601 = Duration::from_nanos(self.0 % Self::TICKS_PER_SECOND * 100)
+            + Duration::from_secs(self.0 / Self::TICKS_PER_SECOND);
+        (UNIX_EPOCH - Duration::from_secs(Self::UNIX_EPOCH_SECONDS)).checked_add(since_1601)
+    }
+
+    /// Creates a [FileTime][crate::FileTime] from the amount of seconds since January 1, 1970.
+    ///
+    /// Returns `None` if the time is before January 1, 1601 or too far in the future to be
+    /// represented.
+    pub fn from_unix_time(seconds: i64) -> Option<Self> {
+        let seconds = seconds.checked_add(Self::UNIX_EPOCH_SECONDS as i64)?;
+        u64::try_from(seconds)
+            .ok()?
+            .checked_mul(Self::TICKS_PER_SECOND)
+            .and_then(Self::from_ticks)
+    }
+
+    /// The amount of seconds since January 1, 1970, rounded down.
+    pub fn to_unix_time(&self) -> i64 {
+        (self.0 / Self::TICKS_PER_SECOND) as i64 - Self::UNIX_EPOCH_SECONDS as i64
+    }
+}
+
+impl From<FileTime> for u64 {
+    fn from(time: FileTime) -> Self {
+        time.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // January 1, 2000 (UTC)
+    const Y2K_UNIX: i64 = 946_684_800;
+    const Y2K_TICKS: u64 = 125_911_584_000_000_000;
+
+    #[test]
+    fn from_ticks_bounds() {
+        assert_eq!(FileTime::from_ticks(0), Some(FileTime(0)));
+        assert_eq!(
+            FileTime::from_ticks(i64::MAX as u64),
+            Some(FileTime(i64::MAX as u64))
+        );
+        assert_eq!(FileTime::from_ticks(i64::MAX as u64 + 1), None);
+    }
+
+    #[test]
+    fn unix_time_round_trip() {
+        let time = FileTime::from_unix_time(Y2K_UNIX).unwrap();
+        assert_eq!(time.ticks(), Y2K_TICKS);
+        assert_eq!(time.to_unix_time(), Y2K_UNIX);
+
+        let epoch = FileTime::from_unix_time(0).unwrap();
+        assert_eq!(epoch.ticks(), 116_444_736_000_000_000);
+        assert_eq!(epoch.to_unix_time(), 0);
+    }
+
+    #[test]
+    fn unix_time_out_of_range() {
+        assert_eq!(FileTime::from_unix_time(-11_644_473_600), Some(FileTime(0)));
+        assert_eq!(FileTime::from_unix_time(-11_644_473_601), None);
+        assert_eq!(FileTime::from_unix_time(i64::MAX), None);
+    }
+
+    #[test]
+    fn to_unix_time_rounds_down() {
+        let time = FileTime::from_ticks(Y2K_TICKS + FileTime::TICKS_PER_SECOND - 1).unwrap();
+        assert_eq!(time.to_unix_time(), Y2K_UNIX);
+    }
+
+    #[test]
+    fn system_time_round_trip() {
+        let system_time = UNIX_EPOCH + Duration::from_secs(Y2K_UNIX as u64);
+        let time = FileTime::from_system_time(system_time).unwrap();
+        assert_eq!(time.ticks(), Y2K_TICKS);
+        assert_eq!(time.to_system_time(), Some(system_time));
+    }
+
+    #[test]
+    fn from_system_time_truncates_precision() {
+        let system_time =
+            UNIX_EPOCH + Duration::from_secs(Y2K_UNIX as u64) + Duration::from_nanos(199);
+        let time = FileTime::from_system_time(system_time).unwrap();
+        assert_eq!(time.ticks(), Y2K_TICKS + 1);
+    }
+
+    #[test]
+    fn from_system_time_before_unix_epoch() {
+        let system_time = UNIX_EPOCH - Duration::from_secs(FileTime::UNIX_EPOCH_SECONDS);
+        assert_eq!(FileTime::from_system_time(system_time), Some(FileTime(0)));
+    }
+}
@@ -0,0 +1,211 @@
+use std::{
+    any::Any,
+    collections::HashMap,
+    fmt, mem,
+    panic::{self, AssertUnwindSafe},
+    sync::{Arc, Condvar, Mutex, Weak},
+};
+
+use crate::{
+    buffer_pool::BufferPool,
+    command::Fallible,
+    error::CloudErrorKind,
+    request::{RawConnectionKey, RawTransferKey},
+};
+
+/// The state shared between a [Connection][crate::Connection] and its callbacks, passed to the
+/// operating system as the callback context.
+pub(crate) struct Context<T> {
+    pub(crate) filter: Weak<T>,
+    pub(crate) inflight: Arc<Inflight>,
+    pub(crate) panic_handler: Option<PanicHandler>,
+    pub(crate) limit: Option<Semaphore>,
+    pub(crate) buffer_pool: Option<Arc<dyn BufferPool>>,
+}
+
+impl<T> Context<T> {
+    /// Runs a callback that was handed a ticket, failing the operation with
+    /// [CloudErrorKind::Unsuccessful][crate::CloudErrorKind::Unsuccessful] if it panics.
+    pub(crate) fn run<C: Fallible>(
+        &self,
+        connection_key: RawConnectionKey,
+        transfer_key: RawTransferKey,
+        callback: impl FnOnce(),
+    ) {
+        let _permit = self.limit.as_ref().map(Semaphore::acquire);
+        if let Err(payload) = panic::catch_unwind(AssertUnwindSafe(callback)) {
+            abort::<C>(connection_key, transfer_key, CloudErrorKind::Unsuccessful);
+            self.panicked(payload);
+        }
+    }
+
+    /// Runs a callback that is only a notification, so there is no operation to fail.
+    pub(crate) fn run_notification(&self, callback: impl FnOnce()) {
+        if let Err(payload) = panic::catch_unwind(AssertUnwindSafe(callback)) {
+            self.panicked(payload);
+        }
+    }
+
+    fn panicked(&self, payload: Box<dyn Any + Send>) {
+        if let Some(handler) = &self.panic_handler {
+            // unwinding into the operating system is undefined behavior, so the handler must not
+            // panic either
+            let _ = panic::catch_unwind(AssertUnwindSafe(|| handler(&*payload)));
+        }
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for Context<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Context")
+            .field("filter", &self.filter)
+            .field("inflight", &self.inflight)
+            .field("panic_handler", &self.panic_handler.is_some())
+            .field("limit", &self.limit)
+            .field("buffer_pool", &self.buffer_pool.is_some())
+            .finish()
+    }
+}
+
+/// Limits the amount of callbacks running at once.
+#[derive(Debug)]
+pub(crate) struct Semaphore {
+    available: Mutex<usize>,
+    released: Condvar,
+}
+
+impl Semaphore {
+    pub(crate) fn new(permits: usize) -> Self {
+        assert!(permits != 0, "the amount of permits must be non-zero");
+        Self {
+            available: Mutex::new(permits),
+            released: Condvar::new(),
+        }
+    }
+
+    /// Blocks until a permit is available, which is released when the returned guard is dropped.
+    fn acquire(&self) -> Permit<'_> {
+        let mut available = self
+            .released
+            .wait_while(self.available.lock().unwrap(), |available| *available == 0)
+            .unwrap();
+        *available -= 1;
+
+        Permit(self)
+    }
+}
+
+struct Permit<'a>(&'a Semaphore);
+
+impl Drop for Permit<'_> {
+    fn drop(&mut self) {
+        *self.0.available.lock().unwrap() += 1;
+        self.0.released.notify_one();
+    }
+}
+
+/// A handler called with the payload of a panic that occurred within a callback.
+pub(crate) type PanicHandler = Arc<dyn Fn(&(dyn Any + Send)) + Send + Sync>;
+
+// fails an operation of a specific type, so it could be aborted without knowing its ticket
+type Abort = fn(RawConnectionKey, RawTransferKey, CloudErrorKind);
+
+/// A registry of the operations that have been handed to the filter but not yet completed.
+#[derive(Debug, Default)]
+pub(crate) struct Inflight(Mutex<HashMap<RawTransferKey, Abort>>);
+
+impl Inflight {
+    /// Tracks the operation until the returned guard is dropped.
+    pub(crate) fn track<C: Fallible>(self: &Arc<Self>, transfer_key: RawTransferKey) -> Tracked {
+        self.0
+            .lock()
+            .unwrap()
+            .insert(transfer_key, abort::<C> as Abort);
+
+        Tracked {
+            inflight: self.clone(),
+            transfer_key,
+        }
+    }
+
+    /// The amount of operations that have not yet completed.
+    pub(crate) fn len(&self) -> usize {
+        self.0.lock().unwrap().len()
+    }
+
+    /// Fails every operation that has not yet completed with the specified error.
+    pub(crate) fn abort_all(&self, connection_key: RawConnectionKey, error_kind: CloudErrorKind) {
+        // take the operations out first so the lock isn't held while executing
+        let operations = mem::take(&mut *self.0.lock().unwrap());
+        for (transfer_key, abort) in operations {
+            abort(connection_key, transfer_key, error_kind);
+        }
+    }
+}
+
+/// Removes an operation from the inflight registry when dropped, which happens when its ticket is
+/// dropped.
+#[derive(Debug)]
+pub(crate) struct Tracked {
+    inflight: Arc<Inflight>,
+    transfer_key: RawTransferKey,
+}
+
+impl Drop for Tracked {
+    fn drop(&mut self) {
+        self.inflight.0.lock().unwrap().remove(&self.transfer_key);
+    }
+}
+
+fn abort<C: Fallible>(
+    connection_key: RawConnectionKey,
+    transfer_key: RawTransferKey,
+    error_kind: CloudErrorKind,
+) {
+    // the operation could have been completed in the meantime, in which case failing it is
+    // expected to error
+    #[allow(unused_must_use)]
+    {
+        C::fail(connection_key, transfer_key, error_kind);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::atomic::{AtomicUsize, Ordering},
+        thread,
+        time::Duration,
+    };
+
+    use super::*;
+
+    #[test]
+    fn semaphore_limits_concurrent_callbacks() {
+        let semaphore = Semaphore::new(2);
+        let running = AtomicUsize::new(0);
+        let max_running = AtomicUsize::new(0);
+
+        thread::scope(|scope| {
+            for _ in 0..8 {
+                scope.spawn(|| {
+                    let _permit = semaphore.acquire();
+                    let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+                    max_running.fetch_max(now, Ordering::SeqCst);
+                    thread::sleep(Duration::from_millis(20));
+                    running.fetch_sub(1, Ordering::SeqCst);
+                });
+            }
+        });
+
+        assert!(max_running.load(Ordering::SeqCst) <= 2);
+        // every permit was returned
+        assert_eq!(*semaphore.available.lock().unwrap(), 2);
+    }
+
+    #[test]
+    #[should_panic(expected = "the amount of permits must be non-zero")]
+    fn semaphore_rejects_zero_permits() {
+        Semaphore::new(0);
+    }
+}
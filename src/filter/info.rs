@@ -1,6 +1,8 @@
-use std::{fmt::Debug, ops::Range, path::PathBuf};
+use std::{ffi::OsStr, fmt::Debug, ops::Range, path::PathBuf};
 
 use widestring::U16CStr;
+#[cfg(not(feature = "globs"))]
+use widestring::U16String;
 use windows::Win32::Storage::CloudFilters::{
     self, CF_CALLBACK_CANCEL_FLAGS, CF_CALLBACK_DEHYDRATION_REASON, CF_CALLBACK_PARAMETERS_0_0,
     CF_CALLBACK_PARAMETERS_0_1, CF_CALLBACK_PARAMETERS_0_10, CF_CALLBACK_PARAMETERS_0_11,
@@ -9,6 +11,8 @@ use windows::Win32::Storage::CloudFilters::{
     CF_CALLBACK_PARAMETERS_0_8, CF_CALLBACK_PARAMETERS_0_9,
 };
 
+use crate::file_time::FileTime;
+
 /// Information for the [SyncFilter::fetch_data][crate::SyncFilter::fetch_data] callback.
 #[derive(Debug, Clone, Copy)]
 pub struct FetchData(pub(crate) CF_CALLBACK_PARAMETERS_0_6);
@@ -39,11 +43,43 @@ impl FetchData {
             ..(self.0.OptionalFileOffset + self.0.OptionalLength) as u64
     }
 
-    /// The last time the file was dehydrated.
+    /// Whether or not the required range is empty, meaning no data has to be transferred.
+    ///
+    /// The operating system occasionally invokes
+    /// [SyncFilter::fetch_data][crate::SyncFilter::fetch_data] with an empty required range, e.g.
+    /// when prefetching metadata. In this case, no data should be written.
+    pub fn is_metadata_only(&self) -> bool {
+        self.required_file_range().is_empty()
+    }
+
+    /// Whether or not the data is being fetched for a directory, which is always `false`.
     ///
-    /// This value is a count of 100-nanosecond intervals since January 1, 1601.
-    pub fn last_dehydration_time(&self) -> u64 {
-        self.0.LastDehydrationTime as u64
+    /// [SyncFilter::fetch_data][crate::SyncFilter::fetch_data] is never invoked for directories,
+    /// their contents are requested through
+    /// [SyncFilter::fetch_placeholders][crate::SyncFilter::fetch_placeholders] regardless of the
+    /// [PopulationType][crate::PopulationType].
+    pub fn is_for_directory(&self) -> bool {
+        false
+    }
+
+    /// The range of data to fetch, choosing between the required and optional file ranges.
+    ///
+    /// The optional range is returned if `prefer_optional` is set and the range is non-empty,
+    /// otherwise the required range is returned. Fetching the optional range results in fewer,
+    /// larger transfers at the cost of the initial request taking longer to complete, which is
+    /// favorable for remotes with a high per-request latency.
+    pub fn fetch_range(&self, prefer_optional: bool) -> Range<u64> {
+        let optional = self.optional_file_range();
+        if prefer_optional && !optional.is_empty() {
+            optional
+        } else {
+            self.required_file_range()
+        }
+    }
+
+    /// The last time the file was dehydrated.
+    pub fn last_dehydration_time(&self) -> FileTime {
+        FileTime(self.0.LastDehydrationTime as u64)
     }
 
     /// The reason the file was last dehydrated.
@@ -76,6 +112,21 @@ impl CancelFetchData {
         let range = unsafe { self.0.Anonymous.FetchData };
         (range.FileOffset as u64)..(range.FileOffset + range.Length) as u64
     }
+
+    /// The part of [CancelFetchData::file_range][crate::info::CancelFetchData::file_range] that
+    /// was abandoned, given that the data up until the `already_written` offset has been
+    /// transferred.
+    ///
+    /// Data that was already transferred is kept by the operating system, leaving the placeholder
+    /// partially hydrated, so it won't be requested again. Only the returned range is discarded
+    /// and will be requested by a later
+    /// [SyncFilter::fetch_data][crate::SyncFilter::fetch_data] callback if needed. Returns `None`
+    /// if the entire range was transferred.
+    pub fn remaining_range(&self, already_written: u64) -> Option<Range<u64>> {
+        let range = self.file_range();
+        let remaining = range.start.max(already_written)..range.end;
+        (!remaining.is_empty()).then_some(remaining)
+    }
 }
 
 impl Debug for CancelFetchData {
@@ -139,6 +190,73 @@ impl FetchPlaceholders {
     pub fn pattern(&self) -> &U16CStr {
         unsafe { U16CStr::from_ptr_str(self.0.Pattern.0) }
     }
+
+    /// Whether or not the file name matches the [pattern][crate::info::FetchPlaceholders::pattern].
+    ///
+    /// Like the file system, the match is case-insensitive and an empty or invalid pattern matches
+    /// every name. This could be used to filter the list of candidate placeholders before
+    /// creating them.
+    #[cfg(feature = "globs")]
+    pub fn matches(&self, name: &OsStr) -> bool {
+        let pattern = unsafe { U16CStr::from_ptr_str(self.0.Pattern.0) }.to_string_lossy();
+        if pattern.is_empty() {
+            return true;
+        }
+
+        globset::GlobBuilder::new(&pattern)
+            .case_insensitive(true)
+            .build()
+            .map_or(true, |glob| glob.compile_matcher().is_match(name))
+    }
+
+    /// Whether or not the file name matches the [pattern][crate::info::FetchPlaceholders::pattern].
+    ///
+    /// Like the file system, the match is case-insensitive and an empty pattern matches every
+    /// name. Only the `*` and `?` wildcards are supported. This could be used to filter the list
+    /// of candidate placeholders before creating them.
+    #[cfg(not(feature = "globs"))]
+    pub fn matches(&self, name: &OsStr) -> bool {
+        let pattern = self.pattern().as_slice();
+        pattern.is_empty() || wildcard_match(pattern, U16String::from_os_str(name).as_slice())
+    }
+}
+
+/// Matches the name against a pattern containing `*` and `?` wildcards, ignoring ASCII case.
+#[cfg(not(feature = "globs"))]
+fn wildcard_match(pattern: &[u16], name: &[u16]) -> bool {
+    const ANY: u16 = b'*' as u16;
+    const ONE: u16 = b'?' as u16;
+
+    let fold = |c: u16| match u8::try_from(c) {
+        Ok(c) => c.to_ascii_lowercase() as u16,
+        Err(_) => c,
+    };
+
+    let (mut p, mut n) = (0, 0);
+    // the position of the last `*` in the pattern and the name position it was tried at
+    let mut backtrack = None;
+    while n < name.len() {
+        match pattern.get(p) {
+            Some(&ANY) => {
+                backtrack = Some((p, n));
+                p += 1;
+            }
+            Some(&c) if c == ONE || fold(c) == fold(name[n]) => {
+                p += 1;
+                n += 1;
+            }
+            _ => match backtrack {
+                Some((star, start)) => {
+                    p = star + 1;
+                    n = start + 1;
+                    backtrack = Some((star, start + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+
+    pattern[p..].iter().all(|&c| c == ANY)
 }
 
 /// Information for the
@@ -179,6 +297,11 @@ struct CancelFetchPlaceholdersDebug {
 }
 
 /// Information for the [SyncFilter::opened][crate::SyncFilter::opened] callback.
+///
+/// The operating system does not report the access the handle was opened with, so read-only
+/// opens can not be told apart from opens for write. Providers that upload on close could instead
+/// compare the state of the file in [SyncFilter::closed][crate::SyncFilter::closed], e.g. whether
+/// it is still [in sync][crate::ext::PlaceholderInfo::is_synced].
 #[derive(Debug, Clone, Copy)]
 pub struct Opened(pub(crate) CF_CALLBACK_PARAMETERS_0_8);
 
@@ -233,10 +356,24 @@ impl Dehydrated {
     }
 
     /// Whether or not the placeholder was already hydrated.
+    ///
+    /// The flag describes the placeholder before the dehydration, if it is set, the placeholder
+    /// held data that has now been discarded. Otherwise, the placeholder had no data to discard,
+    /// read [Dehydrated::became_dehydrated][crate::info::Dehydrated::became_dehydrated].
     pub fn already_hydrated(&self) -> bool {
         (self.0.Flags & CloudFilters::CF_CALLBACK_DEHYDRATE_COMPLETION_FLAG_DEHYDRATED).0 != 0
     }
 
+    /// Whether or not the placeholder went from hydrated to dehydrated, useful for invalidating a
+    /// local cache of hydration states.
+    ///
+    /// This is `false` if the placeholder was already dehydrated, in which case nothing changed.
+    /// The transition is reported regardless of whether it was performed by a
+    /// [background][crate::info::Dehydrated::background] service.
+    pub fn became_dehydrated(&self) -> bool {
+        self.already_hydrated()
+    }
+
     /// The reason the file is being dehydrated.
     pub fn reason(&self) -> Option<DehydrationReason> {
         DehydrationReason::from_win32(self.0.Reason)
@@ -292,6 +429,13 @@ impl Rename {
                 .into()
         }
     }
+
+    /// Where the placeholder is being moved relative to the sync root, derived from
+    /// [Rename::source_in_scope][crate::info::Rename::source_in_scope] and
+    /// [Rename::target_in_scope][crate::info::Rename::target_in_scope].
+    pub fn kind(&self) -> RenameKind {
+        RenameKind::new(self.source_in_scope(), self.target_in_scope())
+    }
 }
 
 /// Information for the [SyncFilter::renamed][crate::SyncFilter::renamed] callback.
@@ -309,6 +453,31 @@ impl Renamed {
     }
 }
 
+/// Where a placeholder is being moved relative to the sync root, read
+/// [Rename::kind][crate::info::Rename::kind].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RenameKind {
+    /// The placeholder is being moved within the sync root.
+    WithinRoot,
+    /// A file/directory is being moved into the sync root from outside of it.
+    IntoRoot,
+    /// The placeholder is being moved out of the sync root.
+    OutOfRoot,
+    /// Neither the source nor the target is within the sync root.
+    Outside,
+}
+
+impl RenameKind {
+    fn new(source_in_scope: bool, target_in_scope: bool) -> Self {
+        match (source_in_scope, target_in_scope) {
+            (true, true) => Self::WithinRoot,
+            (false, true) => Self::IntoRoot,
+            (true, false) => Self::OutOfRoot,
+            (false, false) => Self::Outside,
+        }
+    }
+}
+
 /// The reason a placeholder has been dehydrated.
 #[derive(Debug, Clone, Copy)]
 pub enum DehydrationReason {
@@ -337,3 +506,206 @@ impl DehydrationReason {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use windows::Win32::Storage::CloudFilters::{
+        CF_CALLBACK_PARAMETERS_0_0_0, CF_CALLBACK_PARAMETERS_0_0_0_0,
+    };
+
+    use super::*;
+
+    #[cfg(not(feature = "globs"))]
+    fn matches(pattern: &str, name: &str) -> bool {
+        wildcard_match(
+            &pattern.encode_utf16().collect::<Vec<_>>(),
+            &name.encode_utf16().collect::<Vec<_>>(),
+        )
+    }
+
+    #[test]
+    #[cfg(not(feature = "globs"))]
+    fn wildcard_match_literal() {
+        assert!(matches("file.txt", "file.txt"));
+        assert!(!matches("file.txt", "file.txt2"));
+        assert!(!matches("file.txt", "file.tx"));
+        assert!(!matches("file.txt", ""));
+    }
+
+    #[test]
+    #[cfg(not(feature = "globs"))]
+    fn wildcard_match_ignores_ascii_case() {
+        assert!(matches("FILE.txt", "file.TXT"));
+        assert!(matches("*.TXT", "notes.txt"));
+        // only ASCII is folded
+        assert!(!matches("Ä", "ä"));
+    }
+
+    #[test]
+    #[cfg(not(feature = "globs"))]
+    fn wildcard_match_any() {
+        assert!(matches("*", ""));
+        assert!(matches("*", "file.txt"));
+        assert!(matches("*.txt", ".txt"));
+        assert!(matches("*.txt", "file.txt"));
+        assert!(!matches("*.txt", "file.txt.bak"));
+        assert!(matches("file*", "file"));
+        assert!(matches("f*e*.t*t", "file.txt"));
+        assert!(matches("**", "file.txt"));
+    }
+
+    #[test]
+    #[cfg(not(feature = "globs"))]
+    fn wildcard_match_backtracks() {
+        assert!(matches("*ab", "aab"));
+        assert!(matches("*a*b", "xaxxab"));
+        assert!(!matches("*a*b", "xaxxa"));
+        assert!(matches("a*b*c", "abbbcbc"));
+    }
+
+    #[test]
+    #[cfg(not(feature = "globs"))]
+    fn wildcard_match_one() {
+        assert!(matches("file.???", "file.txt"));
+        assert!(!matches("file.???", "file.md"));
+        assert!(!matches("?", ""));
+        assert!(matches("?*", "a"));
+    }
+
+    fn fetch_data(required: Range<i64>, optional: Range<i64>) -> FetchData {
+        FetchData(CF_CALLBACK_PARAMETERS_0_6 {
+            RequiredFileOffset: required.start,
+            RequiredLength: required.end - required.start,
+            OptionalFileOffset: optional.start,
+            OptionalLength: optional.end - optional.start,
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn fetch_range_prefers_optional() {
+        let info = fetch_data(4096..8192, 0..65536);
+        assert_eq!(info.fetch_range(true), 0..65536);
+        assert_eq!(info.fetch_range(false), 4096..8192);
+    }
+
+    #[test]
+    fn fetch_range_falls_back_to_required() {
+        let info = fetch_data(4096..8192, 0..0);
+        assert_eq!(info.fetch_range(true), 4096..8192);
+        assert_eq!(info.fetch_range(false), 4096..8192);
+    }
+
+    #[test]
+    fn fetch_range_metadata_only() {
+        let info = fetch_data(0..0, 0..0);
+        assert!(info.is_metadata_only());
+        assert!(info.fetch_range(true).is_empty());
+    }
+
+    fn cancel_fetch_data(range: Range<i64>) -> CancelFetchData {
+        CancelFetchData(CF_CALLBACK_PARAMETERS_0_0 {
+            Anonymous: CF_CALLBACK_PARAMETERS_0_0_0 {
+                FetchData: CF_CALLBACK_PARAMETERS_0_0_0_0 {
+                    FileOffset: range.start,
+                    Length: range.end - range.start,
+                },
+            },
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn remaining_range_nothing_written() {
+        let info = cancel_fetch_data(4096..8192);
+        assert_eq!(info.remaining_range(0), Some(4096..8192));
+        assert_eq!(info.remaining_range(4096), Some(4096..8192));
+    }
+
+    #[test]
+    fn remaining_range_partially_written() {
+        let info = cancel_fetch_data(4096..8192);
+        assert_eq!(info.remaining_range(6144), Some(6144..8192));
+    }
+
+    #[test]
+    fn remaining_range_fully_written() {
+        let info = cancel_fetch_data(4096..8192);
+        assert_eq!(info.remaining_range(8192), None);
+        assert_eq!(info.remaining_range(u64::MAX), None);
+    }
+
+    #[test]
+    fn rename_kind_from_scope() {
+        assert_eq!(RenameKind::new(true, true), RenameKind::WithinRoot);
+        assert_eq!(RenameKind::new(false, true), RenameKind::IntoRoot);
+        assert_eq!(RenameKind::new(true, false), RenameKind::OutOfRoot);
+        assert_eq!(RenameKind::new(false, false), RenameKind::Outside);
+    }
+
+    #[test]
+    fn rename_kind_from_flags() {
+        let flags = [
+            (
+                CloudFilters::CF_CALLBACK_RENAME_FLAG_NONE,
+                RenameKind::Outside,
+            ),
+            (
+                CloudFilters::CF_CALLBACK_RENAME_FLAG_SOURCE_IN_SCOPE,
+                RenameKind::OutOfRoot,
+            ),
+            (
+                CloudFilters::CF_CALLBACK_RENAME_FLAG_TARGET_IN_SCOPE,
+                RenameKind::IntoRoot,
+            ),
+            (
+                CloudFilters::CF_CALLBACK_RENAME_FLAG_SOURCE_IN_SCOPE
+                    | CloudFilters::CF_CALLBACK_RENAME_FLAG_TARGET_IN_SCOPE,
+                RenameKind::WithinRoot,
+            ),
+        ];
+        for (flags, kind) in flags {
+            let info = Rename(CF_CALLBACK_PARAMETERS_0_10 {
+                Flags: flags,
+                ..Default::default()
+            });
+            assert_eq!(info.kind(), kind);
+        }
+    }
+
+    #[test]
+    fn dehydrated_flag_combinations() {
+        let flags = [
+            (
+                CloudFilters::CF_CALLBACK_DEHYDRATE_COMPLETION_FLAG_NONE,
+                false,
+                false,
+            ),
+            (
+                CloudFilters::CF_CALLBACK_DEHYDRATE_COMPLETION_FLAG_BACKGROUND,
+                true,
+                false,
+            ),
+            (
+                CloudFilters::CF_CALLBACK_DEHYDRATE_COMPLETION_FLAG_DEHYDRATED,
+                false,
+                true,
+            ),
+            (
+                CloudFilters::CF_CALLBACK_DEHYDRATE_COMPLETION_FLAG_BACKGROUND
+                    | CloudFilters::CF_CALLBACK_DEHYDRATE_COMPLETION_FLAG_DEHYDRATED,
+                true,
+                true,
+            ),
+        ];
+        for (flags, background, became_dehydrated) in flags {
+            let info = Dehydrated(CF_CALLBACK_PARAMETERS_0_2 {
+                Flags: flags,
+                ..Default::default()
+            });
+            assert_eq!(info.background(), background);
+            assert_eq!(info.already_hydrated(), became_dehydrated);
+            assert_eq!(info.became_dehydrated(), became_dehydrated);
+        }
+    }
+}
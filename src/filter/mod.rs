@@ -1,3 +1,4 @@
+mod context;
 /// Information for callbacks in the [SyncFilter][crate::SyncFilter] trait.
 pub mod info;
 mod proxy;
@@ -5,5 +6,6 @@ mod sync_filter;
 /// Tickets for callbacks in the [SyncFilter][crate::SyncFilter] trait.
 pub mod ticket;
 
+pub(crate) use context::{Context, Inflight, PanicHandler, Semaphore};
 pub use proxy::{callbacks, Callbacks};
 pub use sync_filter::SyncFilter;
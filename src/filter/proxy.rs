@@ -1,13 +1,14 @@
 #![allow(clippy::missing_safety_doc)]
 
-use std::sync::{Arc, Weak};
+use std::sync::Arc;
 
 use windows::Win32::Storage::CloudFilters::{
     self, CF_CALLBACK_INFO, CF_CALLBACK_PARAMETERS, CF_CALLBACK_REGISTRATION,
 };
 
 use crate::{
-    filter::{info, ticket, SyncFilter},
+    command,
+    filter::{info, ticket, Context, SyncFilter},
     request::Request,
 };
 
@@ -80,15 +81,22 @@ pub unsafe extern "system" fn fetch_data<T: SyncFilter + 'static>(
     info: *const CF_CALLBACK_INFO,
     params: *const CF_CALLBACK_PARAMETERS,
 ) {
-    if let Some(filter) = filter_from_info::<T>(info) {
+    if let Some((context, filter)) = context_from_info::<T>(info) {
         let request = Request::new(*info);
-        let ticket = ticket::FetchData::new(request.connection_key(), request.transfer_key());
+        let (connection_key, transfer_key) = (request.connection_key(), request.transfer_key());
+        let mut ticket = ticket::FetchData::new(connection_key, transfer_key)
+            .tracked(context.inflight.track::<command::Write>(transfer_key));
+        if let Some(pool) = &context.buffer_pool {
+            ticket = ticket.buffer_pool(pool.clone());
+        }
 
-        filter.fetch_data(
-            request,
-            ticket,
-            info::FetchData((*params).Anonymous.FetchData),
-        );
+        context.run::<command::Write>(connection_key, transfer_key, || {
+            filter.fetch_data(
+                request,
+                ticket,
+                info::FetchData((*params).Anonymous.FetchData),
+            );
+        });
     }
 }
 
@@ -96,15 +104,19 @@ pub unsafe extern "system" fn validate_data<T: SyncFilter + 'static>(
     info: *const CF_CALLBACK_INFO,
     params: *const CF_CALLBACK_PARAMETERS,
 ) {
-    if let Some(filter) = filter_from_info::<T>(info) {
+    if let Some((context, filter)) = context_from_info::<T>(info) {
         let request = Request::new(*info);
-        let ticket = ticket::ValidateData::new(request.connection_key(), request.transfer_key());
+        let (connection_key, transfer_key) = (request.connection_key(), request.transfer_key());
+        let ticket = ticket::ValidateData::new(connection_key, transfer_key)
+            .tracked(context.inflight.track::<command::Validate>(transfer_key));
 
-        filter.validate_data(
-            request,
-            ticket,
-            info::ValidateData((*params).Anonymous.ValidateData),
-        );
+        context.run::<command::Validate>(connection_key, transfer_key, || {
+            filter.validate_data(
+                request,
+                ticket,
+                info::ValidateData((*params).Anonymous.ValidateData),
+            );
+        });
     }
 }
 
@@ -112,11 +124,13 @@ pub unsafe extern "system" fn cancel_fetch_data<T: SyncFilter + 'static>(
     info: *const CF_CALLBACK_INFO,
     params: *const CF_CALLBACK_PARAMETERS,
 ) {
-    if let Some(filter) = filter_from_info::<T>(info) {
-        filter.cancel_fetch_data(
-            Request::new(*info),
-            info::CancelFetchData((*params).Anonymous.Cancel),
-        );
+    if let Some((context, filter)) = context_from_info::<T>(info) {
+        context.run_notification(|| {
+            filter.cancel_fetch_data(
+                Request::new(*info),
+                info::CancelFetchData((*params).Anonymous.Cancel),
+            );
+        });
     }
 }
 
@@ -124,16 +138,23 @@ pub unsafe extern "system" fn fetch_placeholders<T: SyncFilter + 'static>(
     info: *const CF_CALLBACK_INFO,
     params: *const CF_CALLBACK_PARAMETERS,
 ) {
-    if let Some(filter) = filter_from_info::<T>(info) {
+    if let Some((context, filter)) = context_from_info::<T>(info) {
         let request = Request::new(*info);
-        let ticket =
-            ticket::FetchPlaceholders::new(request.connection_key(), request.transfer_key());
+        let (connection_key, transfer_key) = (request.connection_key(), request.transfer_key());
+        let ticket = ticket::FetchPlaceholders::new(connection_key, transfer_key, request.path())
+            .tracked(
+                context
+                    .inflight
+                    .track::<command::CreatePlaceholders>(transfer_key),
+            );
 
-        filter.fetch_placeholders(
-            request,
-            ticket,
-            info::FetchPlaceholders((*params).Anonymous.FetchPlaceholders),
-        );
+        context.run::<command::CreatePlaceholders>(connection_key, transfer_key, || {
+            filter.fetch_placeholders(
+                request,
+                ticket,
+                info::FetchPlaceholders((*params).Anonymous.FetchPlaceholders),
+            );
+        });
     }
 }
 
@@ -141,11 +162,13 @@ pub unsafe extern "system" fn cancel_fetch_placeholders<T: SyncFilter + 'static>
     info: *const CF_CALLBACK_INFO,
     params: *const CF_CALLBACK_PARAMETERS,
 ) {
-    if let Some(filter) = filter_from_info::<T>(info) {
-        filter.cancel_fetch_placeholders(
-            Request::new(*info),
-            info::CancelFetchPlaceholders((*params).Anonymous.Cancel),
-        );
+    if let Some((context, filter)) = context_from_info::<T>(info) {
+        context.run_notification(|| {
+            filter.cancel_fetch_placeholders(
+                Request::new(*info),
+                info::CancelFetchPlaceholders((*params).Anonymous.Cancel),
+            );
+        });
     }
 }
 
@@ -153,11 +176,13 @@ pub unsafe extern "system" fn notify_file_open_completion<T: SyncFilter + 'stati
     info: *const CF_CALLBACK_INFO,
     params: *const CF_CALLBACK_PARAMETERS,
 ) {
-    if let Some(filter) = filter_from_info::<T>(info) {
-        filter.opened(
-            Request::new(*info),
-            info::Opened((*params).Anonymous.OpenCompletion),
-        );
+    if let Some((context, filter)) = context_from_info::<T>(info) {
+        context.run_notification(|| {
+            filter.opened(
+                Request::new(*info),
+                info::Opened((*params).Anonymous.OpenCompletion),
+            );
+        });
     }
 }
 
@@ -165,11 +190,13 @@ pub unsafe extern "system" fn notify_file_close_completion<T: SyncFilter + 'stat
     info: *const CF_CALLBACK_INFO,
     params: *const CF_CALLBACK_PARAMETERS,
 ) {
-    if let Some(filter) = filter_from_info::<T>(info) {
-        filter.closed(
-            Request::new(*info),
-            info::Closed((*params).Anonymous.CloseCompletion),
-        );
+    if let Some((context, filter)) = context_from_info::<T>(info) {
+        context.run_notification(|| {
+            filter.closed(
+                Request::new(*info),
+                info::Closed((*params).Anonymous.CloseCompletion),
+            );
+        });
     }
 }
 
@@ -177,15 +204,19 @@ pub unsafe extern "system" fn notify_dehydrate<T: SyncFilter + 'static>(
     info: *const CF_CALLBACK_INFO,
     params: *const CF_CALLBACK_PARAMETERS,
 ) {
-    if let Some(filter) = filter_from_info::<T>(info) {
+    if let Some((context, filter)) = context_from_info::<T>(info) {
         let request = Request::new(*info);
-        let ticket = ticket::Dehydrate::new(request.connection_key(), request.transfer_key());
+        let (connection_key, transfer_key) = (request.connection_key(), request.transfer_key());
+        let ticket = ticket::Dehydrate::new(connection_key, transfer_key)
+            .tracked(context.inflight.track::<command::Dehydrate>(transfer_key));
 
-        filter.dehydrate(
-            request,
-            ticket,
-            info::Dehydrate((*params).Anonymous.Dehydrate),
-        );
+        context.run::<command::Dehydrate>(connection_key, transfer_key, || {
+            filter.dehydrate(
+                request,
+                ticket,
+                info::Dehydrate((*params).Anonymous.Dehydrate),
+            );
+        });
     }
 }
 
@@ -193,11 +224,13 @@ pub unsafe extern "system" fn notify_dehydrate_completion<T: SyncFilter + 'stati
     info: *const CF_CALLBACK_INFO,
     params: *const CF_CALLBACK_PARAMETERS,
 ) {
-    if let Some(filter) = filter_from_info::<T>(info) {
-        filter.dehydrated(
-            Request::new(*info),
-            info::Dehydrated((*params).Anonymous.DehydrateCompletion),
-        );
+    if let Some((context, filter)) = context_from_info::<T>(info) {
+        context.run_notification(|| {
+            filter.dehydrated(
+                Request::new(*info),
+                info::Dehydrated((*params).Anonymous.DehydrateCompletion),
+            );
+        });
     }
 }
 
@@ -205,11 +238,15 @@ pub unsafe extern "system" fn notify_delete<T: SyncFilter + 'static>(
     info: *const CF_CALLBACK_INFO,
     params: *const CF_CALLBACK_PARAMETERS,
 ) {
-    if let Some(filter) = filter_from_info::<T>(info) {
+    if let Some((context, filter)) = context_from_info::<T>(info) {
         let request = Request::new(*info);
-        let ticket = ticket::Delete::new(request.connection_key(), request.transfer_key());
+        let (connection_key, transfer_key) = (request.connection_key(), request.transfer_key());
+        let ticket = ticket::Delete::new(connection_key, transfer_key)
+            .tracked(context.inflight.track::<command::Delete>(transfer_key));
 
-        filter.delete(request, ticket, info::Delete((*params).Anonymous.Delete));
+        context.run::<command::Delete>(connection_key, transfer_key, || {
+            filter.delete(request, ticket, info::Delete((*params).Anonymous.Delete));
+        });
     }
 }
 
@@ -217,11 +254,13 @@ pub unsafe extern "system" fn notify_delete_completion<T: SyncFilter + 'static>(
     info: *const CF_CALLBACK_INFO,
     params: *const CF_CALLBACK_PARAMETERS,
 ) {
-    if let Some(filter) = filter_from_info::<T>(info) {
-        filter.deleted(
-            Request::new(*info),
-            info::Deleted((*params).Anonymous.DeleteCompletion),
-        );
+    if let Some((context, filter)) = context_from_info::<T>(info) {
+        context.run_notification(|| {
+            filter.deleted(
+                Request::new(*info),
+                info::Deleted((*params).Anonymous.DeleteCompletion),
+            );
+        });
     }
 }
 
@@ -229,11 +268,15 @@ pub unsafe extern "system" fn notify_rename<T: SyncFilter + 'static>(
     info: *const CF_CALLBACK_INFO,
     params: *const CF_CALLBACK_PARAMETERS,
 ) {
-    if let Some(filter) = filter_from_info::<T>(info) {
+    if let Some((context, filter)) = context_from_info::<T>(info) {
         let request = Request::new(*info);
-        let ticket = ticket::Rename::new(request.connection_key(), request.transfer_key());
+        let (connection_key, transfer_key) = (request.connection_key(), request.transfer_key());
+        let ticket = ticket::Rename::new(connection_key, transfer_key)
+            .tracked(context.inflight.track::<command::Rename>(transfer_key));
 
-        filter.rename(request, ticket, info::Rename((*params).Anonymous.Rename));
+        context.run::<command::Rename>(connection_key, transfer_key, || {
+            filter.rename(request, ticket, info::Rename((*params).Anonymous.Rename));
+        });
     }
 }
 
@@ -241,36 +284,20 @@ pub unsafe extern "system" fn notify_rename_completion<T: SyncFilter + 'static>(
     info: *const CF_CALLBACK_INFO,
     params: *const CF_CALLBACK_PARAMETERS,
 ) {
-    if let Some(filter) = filter_from_info::<T>(info) {
-        filter.renamed(
-            Request::new(*info),
-            info::Renamed((*params).Anonymous.RenameCompletion),
-        );
+    if let Some((context, filter)) = context_from_info::<T>(info) {
+        context.run_notification(|| {
+            filter.renamed(
+                Request::new(*info),
+                info::Renamed((*params).Anonymous.RenameCompletion),
+            );
+        });
     }
 }
 
-unsafe fn filter_from_info<T: SyncFilter + 'static>(
+unsafe fn context_from_info<'a, T: SyncFilter + 'static>(
     info: *const CF_CALLBACK_INFO,
-) -> Option<Arc<T>> {
-    // get the original weak arc
-    let weak = Weak::from_raw((*info).CallbackContext as *mut T);
-    // attempt to upgrade it to a strong arc
-    match weak.upgrade() {
-        // if the memory exists then the filter hasn't been disconnected
-        Some(strong) => {
-            // forget the original weak arc for next use
-            let _ = Weak::into_raw(weak);
-            // return the strong arc
-            Some(strong)
-        }
-        // if the memory is freed then the filter is disconnected
-        None => {
-            // TODO: the weak arc should be freed on disconnect making this scenario
-            // relatively impossible. Although, if I free the weak arc and by some chance
-            // a callback is called, then it could be reading memory from a dangling pointer.
-            // I could either introduce some mega hack to free the memory after x seconds or
-            // suffer from a few bytes of memory leak..
-            None
-        }
-    }
+) -> Option<(&'a Context<T>, Arc<T>)> {
+    let context = &*((*info).CallbackContext as *const Context<T>);
+    // if the filter can't be upgraded then it has been disconnected
+    context.filter.upgrade().map(|filter| (context, filter))
 }
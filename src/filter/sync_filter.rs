@@ -1,5 +1,6 @@
 use crate::{
     error::CloudErrorKind,
+    ext::ProviderStatus,
     filter::{info, ticket},
     request::Request,
 };
@@ -12,6 +13,7 @@ pub trait SyncFilter: Send + Sync {
     /// A placeholder hydration has been requested. This means that the placeholder should be
     /// populated with its corresponding data on the remote.
     fn fetch_data(&self, _request: Request, ticket: ticket::FetchData, _info: info::FetchData) {
+        self.on_unimplemented("fetch_data");
         #[allow(unused_must_use)]
         {
             ticket.fail(CloudErrorKind::NotSupported);
@@ -34,6 +36,7 @@ pub trait SyncFilter: Send + Sync {
         ticket: ticket::ValidateData,
         _info: info::ValidateData,
     ) {
+        self.on_unimplemented("validate_data");
         #[allow(unused_must_use)]
         {
             ticket.fail(CloudErrorKind::NotSupported);
@@ -48,6 +51,7 @@ pub trait SyncFilter: Send + Sync {
         ticket: ticket::FetchPlaceholders,
         _info: info::FetchPlaceholders,
     ) {
+        self.on_unimplemented("fetch_placeholders");
         #[allow(unused_must_use)]
         {
             ticket.fail(CloudErrorKind::NotSupported);
@@ -71,6 +75,7 @@ pub trait SyncFilter: Send + Sync {
     /// The operating system will handle dehydrating placeholder files automatically. However, it
     /// is up to **you** to approve this. Use the ticket to approve or disapprove the request.
     fn dehydrate(&self, _request: Request, ticket: ticket::Dehydrate, _info: info::Dehydrate) {
+        self.on_unimplemented("dehydrate");
         #[allow(unused_must_use)]
         {
             ticket.fail(CloudErrorKind::NotSupported);
@@ -85,6 +90,7 @@ pub trait SyncFilter: Send + Sync {
     /// The operating system will handle deleting placeholder files automatically. However, it is
     /// up to **you** to approve this. Use the ticket to approve or disapprove the request.
     fn delete(&self, _request: Request, ticket: ticket::Delete, _info: info::Delete) {
+        self.on_unimplemented("delete");
         #[allow(unused_must_use)]
         {
             ticket.fail(CloudErrorKind::NotSupported);
@@ -102,6 +108,7 @@ pub trait SyncFilter: Send + Sync {
     ///
     /// When the operation is completed, the [SyncFilter::renamed][crate::SyncFilter::renamed] callback will be called.
     fn rename(&self, _request: Request, ticket: ticket::Rename, _info: info::Rename) {
+        self.on_unimplemented("rename");
         #[allow(unused_must_use)]
         {
             ticket.fail(CloudErrorKind::NotSupported);
@@ -110,4 +117,35 @@ pub trait SyncFilter: Send + Sync {
 
     /// A placeholder file has been renamed or moved.
     fn renamed(&self, _request: Request, _info: info::Renamed) {}
+
+    /// The status of the sync provider has changed, e.g. connectivity to the remote was lost.
+    ///
+    /// The status is polled from a background thread while the
+    /// [Connection][crate::Connection] is alive and this callback is only called on transitions.
+    fn status_changed(&self, _status: ProviderStatus) {}
+
+    /// The background thread calling
+    /// [SyncFilter::status_changed][crate::SyncFilter::status_changed] has started, which happens
+    /// once the sync root is connected.
+    ///
+    /// This callback is invoked from the background thread itself.
+    fn watcher_started(&self) {}
+
+    /// The background thread calling
+    /// [SyncFilter::status_changed][crate::SyncFilter::status_changed] has stopped, which happens
+    /// when the [Connection][crate::Connection] is dropped.
+    ///
+    /// If this is called while the connection is still alive, the thread has died, e.g. because
+    /// [SyncFilter::status_changed][crate::SyncFilter::status_changed] panicked, and status
+    /// changes are no longer reported.
+    fn watcher_stopped(&self) {}
+
+    /// A callback that was not implemented has been invoked, where `operation` is the name of the
+    /// callback, e.g. `"fetch_data"`.
+    ///
+    /// The default implementations of the callbacks that are handed a ticket call this method
+    /// before failing the operation with
+    /// [CloudErrorKind::NotSupported][crate::CloudErrorKind::NotSupported], which is useful for
+    /// logging callbacks that were forgotten.
+    fn on_unimplemented(&self, _operation: &'static str) {}
 }
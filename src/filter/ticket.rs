@@ -1,19 +1,53 @@
-use std::ops::Range;
+use std::{
+    ffi::OsString,
+    fmt, io,
+    marker::PhantomData,
+    num::NonZeroU64,
+    ops::Range,
+    os::windows::io::AsRawHandle,
+    path::{Path, PathBuf},
+    sync::{
+        mpsc::{self, RecvTimeoutError, Sender},
+        Arc,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
 
-use windows::core;
+use windows::{
+    core,
+    Win32::{
+        Foundation::{ERROR_THREAD_MODE_ALREADY_BACKGROUND, E_INVALIDARG},
+        Storage::CloudFilters::{self, CfReportProviderProgress, CF_CONNECTION_KEY},
+        System::Threading::{
+            GetCurrentThread, SetThreadPriority, THREAD_MODE_BACKGROUND_BEGIN,
+            THREAD_MODE_BACKGROUND_END,
+        },
+    },
+};
 
 use crate::{
+    buffer_pool::{BufferPool, Pooled},
     command::{self, Command, Fallible},
     error::CloudErrorKind,
+    ext::{file_ranges, FileExt, ReadType},
+    filter::{context::Tracked, info},
     request::{RawConnectionKey, RawTransferKey},
-    PlaceholderFile, Usn,
+    utility::{aligned_chunks, aligned_gaps, io_to_core},
+    walk, Metadata, PlaceholderFile, Usn,
 };
 
+// the size of the buffer used to transfer data from a source, must be a multiple of 4KiB
+const TRANSFER_BUFFER_SIZE: usize = 1024 * 1024;
+// how often a kept alive operation is pinged, well within the 60 second timeout
+const KEEP_ALIVE_INTERVAL: Duration = Duration::from_secs(30);
+
 /// A ticket for the [SyncFilter::fetch_data][crate::SyncFilter::fetch_data] callback.
-#[derive(Debug)]
 pub struct FetchData {
     connection_key: RawConnectionKey,
     transfer_key: RawTransferKey,
+    _tracked: Option<Tracked>,
+    buffer_pool: Option<Arc<dyn BufferPool>>,
 }
 
 impl FetchData {
@@ -22,7 +56,252 @@ impl FetchData {
         Self {
             connection_key,
             transfer_key,
+            _tracked: None,
+            buffer_pool: None,
+        }
+    }
+
+    // takes transfer buffers from the pool of the session, rather than allocating them
+    pub(crate) fn buffer_pool(mut self, pool: Arc<dyn BufferPool>) -> Self {
+        self.buffer_pool = Some(pool);
+        self
+    }
+
+    // removes the operation from the inflight registry when the ticket is dropped
+    pub(crate) fn tracked(mut self, tracked: Tracked) -> Self {
+        self._tracked = Some(tracked);
+        self
+    }
+
+    /// Transfers the requested data from the source, which is read at the same offsets as the
+    /// placeholder, returning the number of bytes transferred.
+    ///
+    /// The [RangeStrategy][crate::ticket::RangeStrategy] determines whether only the required
+    /// range is transferred or the larger optional range, read
+    /// [FetchData::fetch_range][crate::info::FetchData::fetch_range] for more information.
+    ///
+    /// Nothing is transferred if the request is
+    /// [metadata only][crate::info::FetchData::is_metadata_only].
+    pub fn transfer_from(
+        &self,
+        info: &info::FetchData,
+        strategy: RangeStrategy,
+        mut source: impl io::Read + io::Seek,
+    ) -> core::Result<u64> {
+        if info.is_metadata_only() {
+            return Ok(0);
+        }
+
+        let range = info.fetch_range(strategy == RangeStrategy::PreferOptional);
+        source
+            .seek(io::SeekFrom::Start(range.start))
+            .map_err(io_to_core)?;
+
+        self.with_transfer_buffer(range, TRANSFER_BUFFER_SIZE, |buffer| source.read(buffer))
+    }
+
+    /// Transfers the data within the range from the source, skipping the parts that are already
+    /// present on the disk, returning the number of bytes transferred.
+    ///
+    /// This is useful to resume a hydration that was interrupted, e.g. by a lost connection, as
+    /// only the missing data is read from the source, which is read at the same offsets as the
+    /// placeholder at `path`. The missing parts are widened to the transfer alignment, so data
+    /// around them could be transferred again. The range has the same requirements as the range
+    /// passed to
+    /// [FetchData::with_transfer_buffer][crate::ticket::FetchData::with_transfer_buffer].
+    pub fn transfer_from_resumable(
+        &self,
+        path: impl AsRef<Path>,
+        range: Range<u64>,
+        mut source: impl io::Read + io::Seek,
+    ) -> core::Result<u64> {
+        // the placeholder is opened without read access, so it is not hydrated again
+        let file = walk::open_attributes(path.as_ref()).map_err(io_to_core)?;
+        let present = file_ranges(file.as_raw_handle(), ReadType::Saved)?
+            .into_iter()
+            .map(|range| range.StartingOffset as u64..(range.StartingOffset + range.Length) as u64);
+
+        let mut transferred = 0;
+        for gap in aligned_gaps(range, present) {
+            source
+                .seek(io::SeekFrom::Start(gap.start))
+                .map_err(io_to_core)?;
+            transferred +=
+                self.with_transfer_buffer(gap, TRANSFER_BUFFER_SIZE, |buffer| source.read(buffer))?;
+        }
+
+        Ok(transferred)
+    }
+
+    /// Transfers only the required range from a stream that starts at the beginning of that range,
+    /// such as the body of an HTTP range request, returning the number of bytes transferred.
+    ///
+    /// This is the intended way to serve a sync root registered with
+    /// [HydrationType::Progressive][crate::HydrationType::Progressive]. The callback only has to
+    /// satisfy the requested offsets and return, the operating system requests the remaining
+    /// ranges through subsequent calls to
+    /// [SyncFilter::fetch_data][crate::SyncFilter::fetch_data].
+    pub fn transfer_required_from(
+        &self,
+        info: &info::FetchData,
+        mut source: impl io::Read,
+    ) -> core::Result<u64> {
+        if info.is_metadata_only() {
+            return Ok(0);
         }
+
+        self.with_transfer_buffer(info.required_file_range(), TRANSFER_BUFFER_SIZE, |buffer| {
+            source.read(buffer)
+        })
+    }
+
+    /// Transfers the data within the range using a single buffer of `buffer_size` bytes, returning
+    /// the number of bytes transferred.
+    ///
+    /// `fill` reads directly into the unfilled part of the buffer from the source and returns the
+    /// number of bytes read, avoiding an intermediate copy. The buffer is transferred whenever it
+    /// is full or the end of the range is reached. If `fill` returns 0 before the end of the range
+    /// is reached, an error is returned.
+    ///
+    /// The start of the range and `buffer_size` must be 4KiB aligned, while the end of the range
+    /// must be 4KiB aligned or the logical size of the file, as is the case for
+    /// [FetchData::required_file_range][crate::info::FetchData::required_file_range].
+    pub fn with_transfer_buffer(
+        &self,
+        range: Range<u64>,
+        buffer_size: usize,
+        fill: impl FnMut(&mut [u8]) -> io::Result<usize>,
+    ) -> core::Result<u64> {
+        self.with_throttled_transfer_buffer(range, buffer_size, ThrottlePolicy::None, fill)
+    }
+
+    /// Equivalent to [FetchData::with_transfer_buffer][crate::ticket::FetchData::with_transfer_buffer],
+    /// except the transfer is paced according to the specified
+    /// [ThrottlePolicy][crate::ticket::ThrottlePolicy].
+    ///
+    /// The progress of the transfer is reported to the operating system after each chunk, so the
+    /// progress shown to the user stays accurate while the transfer is throttled. Reporting is
+    /// best-effort, a failure to report progress does not fail the transfer.
+    pub fn with_throttled_transfer_buffer(
+        &self,
+        range: Range<u64>,
+        buffer_size: usize,
+        mut throttle: ThrottlePolicy,
+        mut fill: impl FnMut(&mut [u8]) -> io::Result<usize>,
+    ) -> core::Result<u64> {
+        let start = Instant::now();
+        let mut buffer = Pooled::acquire(self.buffer_pool.as_ref(), buffer_size);
+        let mut position = range.start;
+        // the range is transferred as is, so its end is treated as the logical size
+        for chunk in aligned_chunks(range.clone(), buffer_size, range.end) {
+            let len = (chunk.end - chunk.start) as usize;
+            let mut filled = 0;
+            while filled < len {
+                match fill(&mut buffer[filled..len]).map_err(io_to_core)? {
+                    0 => {
+                        return Err(io_to_core(io::Error::new(
+                            io::ErrorKind::UnexpectedEof,
+                            "the source ended before the end of the range",
+                        )))
+                    }
+                    read => filled += read,
+                }
+            }
+
+            command::Write {
+                buffer: &buffer[..len],
+                position,
+            }
+            .execute(self.connection_key, self.transfer_key)?;
+            position += len as u64;
+
+            let transferred = position - range.start;
+            // the data is already written, so a failure to report progress isn't fatal
+            #[allow(unused_must_use)]
+            unsafe {
+                CfReportProviderProgress(
+                    CF_CONNECTION_KEY(self.connection_key),
+                    self.transfer_key,
+                    (range.end - range.start) as i64,
+                    transferred as i64,
+                );
+            }
+
+            if position < range.end {
+                let delay = throttle.delay(transferred, start.elapsed());
+                if !delay.is_zero() {
+                    thread::sleep(delay);
+                }
+            }
+        }
+
+        Ok(position - range.start)
+    }
+
+    /// Restarts the hydration of the placeholder, optionally updating its metadata and blob.
+    ///
+    /// This is useful when the provider discovers the placeholder is stale in the middle of a
+    /// hydration. It is only valid within the
+    /// [SyncFilter::fetch_data][crate::SyncFilter::fetch_data] callback, before the ticket is
+    /// completed. Any data transferred beforehand is discarded and the operating system will
+    /// request it again.
+    ///
+    /// Returns `E_INVALIDARG` if the blob exceeds
+    /// [4KiB](https://microsoft.github.io/windows-docs-rs/doc/windows/Win32/Storage/CloudFilters/constant.CF_PLACEHOLDER_MAX_FILE_IDENTITY_LENGTH.html).
+    pub fn restart_hydration(
+        &self,
+        mark_sync: bool,
+        metadata: Option<Metadata>,
+        blob: Option<&[u8]>,
+    ) -> core::Result<()> {
+        if let Some(blob) = blob {
+            if blob.len() > CloudFilters::CF_PLACEHOLDER_MAX_FILE_IDENTITY_LENGTH as usize {
+                return Err(core::Error::new(
+                    E_INVALIDARG,
+                    format!(
+                        "blob size must not exceed {} bytes, got {} bytes",
+                        CloudFilters::CF_PLACEHOLDER_MAX_FILE_IDENTITY_LENGTH,
+                        blob.len()
+                    )
+                    .into(),
+                ));
+            }
+        }
+
+        command::Update {
+            mark_sync,
+            metadata,
+            blob,
+        }
+        .execute(self.connection_key, self.transfer_key)
+    }
+
+    /// Lowers the CPU, I/O, and memory priority of the current thread until the returned
+    /// [BackgroundPriority][crate::ticket::BackgroundPriority] is dropped.
+    ///
+    /// This uses the background processing mode of the current thread
+    /// (`THREAD_MODE_BACKGROUND_BEGIN`), so the transfer does not starve foreground applications
+    /// of disk bandwidth. It is useful for hydrations that were not requested by the user, such as
+    /// those triggered by [FileExt::hydrate][crate::ext::FileExt::hydrate]. The transfer should be
+    /// performed on the same thread, as the priority only applies to the calling thread.
+    pub fn set_background_priority(&self) -> core::Result<BackgroundPriority> {
+        BackgroundPriority::begin()
+    }
+
+    /// Keeps the operation from timing out until the returned
+    /// [KeepAliveGuard][crate::ticket::KeepAliveGuard] is dropped.
+    ///
+    /// The operating system invalidates an operation after 60 seconds without activity, read
+    /// [Request::reset_timeout][crate::Request::reset_timeout]. The guard spawns a background
+    /// thread that reports progress every 30 seconds, which is useful when a slow remote must be
+    /// waited on before any data could be transferred. The reported progress is empty, so once
+    /// data is being transferred, its progress should be reported instead, e.g. by
+    /// [FetchData::with_transfer_buffer][crate::ticket::FetchData::with_transfer_buffer].
+    ///
+    /// The guard must be dropped once the operation is completed or abandoned, otherwise an
+    /// abandoned operation is kept alive indefinitely.
+    pub fn keep_alive(&self) -> KeepAliveGuard {
+        KeepAliveGuard::spawn(self.connection_key, self.transfer_key)
     }
 
     /// Fail the callback with the specified error.
@@ -31,11 +310,23 @@ impl FetchData {
     }
 }
 
+impl fmt::Debug for FetchData {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FetchData")
+            .field("connection_key", &self.connection_key)
+            .field("transfer_key", &self.transfer_key)
+            .field("_tracked", &self._tracked)
+            .field("buffer_pool", &self.buffer_pool.is_some())
+            .finish()
+    }
+}
+
 /// A ticket for the [SyncFilter::validate_data][crate::SyncFilter::validate_data] callback.
 #[derive(Debug)]
 pub struct ValidateData {
     connection_key: RawConnectionKey,
     transfer_key: RawTransferKey,
+    _tracked: Option<Tracked>,
 }
 
 impl ValidateData {
@@ -44,9 +335,16 @@ impl ValidateData {
         Self {
             connection_key,
             transfer_key,
+            _tracked: None,
         }
     }
 
+    // removes the operation from the inflight registry when the ticket is dropped
+    pub(crate) fn tracked(mut self, tracked: Tracked) -> Self {
+        self._tracked = Some(tracked);
+        self
+    }
+
     // TODO: make this generic over a RangeBounds
     // if the range specified is past the current file length, will it consider that range to be validated?
     // https://docs.microsoft.com/en-us/answers/questions/750302/if-the-ackdata-field-of-cf-operation-parameters-is.html
@@ -55,6 +353,49 @@ impl ValidateData {
         command::Validate { range }.execute(self.connection_key, self.transfer_key)
     }
 
+    /// Confirms the entire range requested by the callback is valid.
+    ///
+    /// This is a shortcut for calling [ValidateData::pass][crate::ticket::ValidateData::pass] with
+    /// [ValidateData::file_range][crate::info::ValidateData::file_range].
+    pub fn pass_all(&self, info: &info::ValidateData) -> core::Result<()> {
+        self.pass(info.file_range())
+    }
+
+    /// Reads the range requested by the callback in chunks, passing each chunk and its offset to
+    /// `verify`, e.g. to compare it against a hash of the remote content.
+    ///
+    /// The range is only confirmed as valid if every chunk is verified, otherwise the callback is
+    /// failed with [CloudErrorKind::ValidationFailed][crate::CloudErrorKind::ValidationFailed] and
+    /// the remaining chunks are not read. The returned value is whether or not the range was
+    /// confirmed.
+    pub fn validate_with(
+        &self,
+        info: &info::ValidateData,
+        mut verify: impl FnMut(&[u8], u64) -> bool,
+    ) -> core::Result<bool> {
+        let range = info.file_range();
+        let mut buffer = vec![0; TRANSFER_BUFFER_SIZE.min((range.end - range.start) as usize)];
+        let mut position = range.start;
+        while position < range.end {
+            let len = buffer.len().min((range.end - position) as usize);
+            let read = command::Read {
+                buffer: &mut buffer[..len],
+                position,
+            }
+            .execute(self.connection_key, self.transfer_key)?;
+
+            // data missing from the placeholder could never be valid
+            if read == 0 || !verify(&buffer[..read as usize], position) {
+                self.fail(CloudErrorKind::ValidationFailed)?;
+                return Ok(false);
+            }
+            position += read;
+        }
+
+        self.pass(range)?;
+        Ok(true)
+    }
+
     /// Fail the callback with the specified error.
     pub fn fail(&self, error_kind: CloudErrorKind) -> core::Result<()> {
         command::Validate::fail(self.connection_key, self.transfer_key, error_kind)
@@ -66,31 +407,126 @@ impl ValidateData {
 pub struct FetchPlaceholders {
     connection_key: RawConnectionKey,
     transfer_key: RawTransferKey,
+    path: PathBuf,
+    _tracked: Option<Tracked>,
 }
 
 impl FetchPlaceholders {
-    /// Create a new [FetchPlaceholders][crate::ticket::FetchPlaceholders].
-    pub fn new(connection_key: RawConnectionKey, transfer_key: RawTransferKey) -> Self {
+    /// Create a new [FetchPlaceholders][crate::ticket::FetchPlaceholders] for the directory at the
+    /// given path.
+    pub fn new(
+        connection_key: RawConnectionKey,
+        transfer_key: RawTransferKey,
+        path: PathBuf,
+    ) -> Self {
         Self {
             connection_key,
             transfer_key,
+            path,
+            _tracked: None,
         }
     }
 
+    // removes the operation from the inflight registry when the ticket is dropped
+    pub(crate) fn tracked(mut self, tracked: Tracked) -> Self {
+        self._tracked = Some(tracked);
+        self
+    }
+
+    /// The absolute path of the directory being populated.
+    ///
+    /// Placeholders could be created within this directory outside of the callback using
+    /// [BatchCreate::create][crate::BatchCreate::create], although they should usually be passed
+    /// to [FetchPlaceholders::pass_with_placeholder][crate::ticket::FetchPlaceholders::pass_with_placeholder].
+    pub fn parent_path(&self) -> &Path {
+        &self.path
+    }
+
     /// Creates a list of placeholder files/directorys on the file system.
     ///
     /// The value returned is the final [Usn][crate::Usn] (and if they succeeded) after each placeholder is created.
     pub fn pass_with_placeholder(
         &self,
         placeholders: &mut [PlaceholderFile],
+    ) -> core::Result<Vec<core::Result<Usn>>> {
+        let total = placeholders.len() as u64;
+        self.pass_partial(placeholders, total, false)
+    }
+
+    /// Equivalent to [FetchPlaceholders::pass_with_placeholder][crate::ticket::FetchPlaceholders::pass_with_placeholder],
+    /// except each result is paired with the relative path of its placeholder, so failures don't
+    /// have to be correlated by index.
+    pub fn pass_with_placeholder_named(
+        &self,
+        placeholders: &mut [PlaceholderFile],
+    ) -> core::Result<Vec<(OsString, core::Result<Usn>)>> {
+        let results = self.pass_with_placeholder(placeholders)?;
+        Ok(placeholders
+            .iter()
+            .map(|placeholder| placeholder.relative_path().into_os_string())
+            .zip(results)
+            .collect())
+    }
+
+    /// Creates a list of placeholder files/directories on the file system, specifying whether or
+    /// not more placeholders are coming.
+    ///
+    /// This method could be called multiple times within the callback to transfer placeholders in
+    /// batches as they become ready. If `more_coming` is `true`, the directory is not marked as
+    /// fully populated, so the operating system will invoke
+    /// [SyncFilter::fetch_placeholders][crate::SyncFilter::fetch_placeholders] again the next
+    /// time the directory is enumerated. This allows a population that takes too long to return
+    /// early with a partial set of placeholders.
+    ///
+    /// `total` is the amount of placeholders expected in the entire listing across every batch,
+    /// not the length of the current batch.
+    pub fn pass_partial(
+        &self,
+        placeholders: &mut [PlaceholderFile],
+        total: u64,
+        more_coming: bool,
     ) -> core::Result<Vec<core::Result<Usn>>> {
         command::CreatePlaceholders {
-            total: placeholders.len() as _,
+            total,
             placeholders,
+            complete: !more_coming,
         }
         .execute(self.connection_key, self.transfer_key)
     }
 
+    /// Creates a list of placeholder files/directories on the file system, resolving conflicts
+    /// with existing files/directories using the specified [ConflictPolicy][crate::ticket::ConflictPolicy].
+    ///
+    /// The policy overrides [PlaceholderFile::overwrite][crate::PlaceholderFile::overwrite]. A
+    /// placeholder that is skipped will have an error in its respective result. Under
+    /// [ConflictPolicy::OverwriteIfInSync][crate::ticket::ConflictPolicy::OverwriteIfInSync], an
+    /// existing entry whose state could not be read is treated as not in sync, so it is kept.
+    pub fn pass_with_placeholder_policy(
+        &self,
+        placeholders: &mut [PlaceholderFile],
+        policy: ConflictPolicy,
+    ) -> core::Result<Vec<core::Result<Usn>>> {
+        for placeholder in placeholders.iter_mut() {
+            let overwrite = match policy {
+                ConflictPolicy::Skip => false,
+                ConflictPolicy::Overwrite => true,
+                ConflictPolicy::OverwriteIfInSync => {
+                    let path = self.path.join(placeholder.relative_path());
+                    // a file that is not a placeholder is never in sync, while a file whose state
+                    // couldn't be read (e.g. it's locked) is kept rather than failing the batch
+                    path.exists()
+                        && walk::open_attributes(&path)
+                            .map_err(io_to_core)
+                            .and_then(|file| file.placeholder_state())
+                            .is_ok_and(|state| state.is_some_and(|state| state.is_synced()))
+                }
+            };
+            placeholder.set_overwrite(overwrite);
+        }
+
+        self.pass_with_placeholder(placeholders)
+    }
+
     /// Fail the callback with the specified error.
     pub fn fail(&self, error_kind: CloudErrorKind) -> core::Result<()> {
         command::CreatePlaceholders::fail(self.connection_key, self.transfer_key, error_kind)
@@ -98,11 +534,160 @@ impl FetchPlaceholders {
     }
 }
 
+/// Determines how to handle a placeholder that conflicts with an existing file/directory, used by
+/// [FetchPlaceholders::pass_with_placeholder_policy][crate::ticket::FetchPlaceholders::pass_with_placeholder_policy].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConflictPolicy {
+    /// Keep the existing file/directory.
+    Skip,
+    /// Replace the existing file/directory.
+    Overwrite,
+    /// Replace the existing file/directory only if it is a placeholder that is in sync, so that
+    /// local modifications are never discarded.
+    OverwriteIfInSync,
+}
+
+/// Determines which range of data is transferred by
+/// [FetchData::transfer_from][crate::ticket::FetchData::transfer_from].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RangeStrategy {
+    /// Only transfer the range required by the operating system.
+    #[default]
+    RequiredOnly,
+    /// Transfer the optional range if the operating system provided one, reducing the amount of
+    /// requests for sequential reads.
+    PreferOptional,
+}
+
+/// Paces a transfer performed by
+/// [FetchData::with_throttled_transfer_buffer][crate::ticket::FetchData::with_throttled_transfer_buffer],
+/// for instance, to avoid saturating a metered connection.
+pub enum ThrottlePolicy {
+    /// Transfer as fast as possible.
+    None,
+    /// Limit the transfer to an average number of bytes per second.
+    BytesPerSecond(NonZeroU64),
+    /// Call the closure after each chunk with the number of bytes transferred so far, sleeping for
+    /// the returned duration.
+    Callback(Box<dyn FnMut(u64) -> Duration + Send>),
+}
+
+impl ThrottlePolicy {
+    fn delay(&mut self, transferred: u64, elapsed: Duration) -> Duration {
+        match self {
+            Self::None => Duration::ZERO,
+            Self::BytesPerSecond(rate) => {
+                Duration::from_secs_f64(transferred as f64 / rate.get() as f64)
+                    .saturating_sub(elapsed)
+            }
+            Self::Callback(callback) => callback(transferred),
+        }
+    }
+}
+
+impl fmt::Debug for ThrottlePolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::None => write!(f, "None"),
+            Self::BytesPerSecond(rate) => f.debug_tuple("BytesPerSecond").field(rate).finish(),
+            Self::Callback(_) => f.debug_tuple("Callback").finish_non_exhaustive(),
+        }
+    }
+}
+
+/// A guard that restores the priority of the current thread when dropped, created by
+/// [FetchData::set_background_priority][crate::ticket::FetchData::set_background_priority].
+///
+/// If the thread was already in background mode, the guard leaves it as is when dropped.
+#[derive(Debug)]
+pub struct BackgroundPriority {
+    restore: bool,
+    // the priority belongs to the thread that created the guard
+    _thread: PhantomData<*const ()>,
+}
+
+impl BackgroundPriority {
+    fn begin() -> core::Result<Self> {
+        let restore =
+            match unsafe { SetThreadPriority(GetCurrentThread(), THREAD_MODE_BACKGROUND_BEGIN) }
+                .ok()
+            {
+                Ok(()) => true,
+                Err(err) if err.win32_error() == Some(ERROR_THREAD_MODE_ALREADY_BACKGROUND) => {
+                    false
+                }
+                Err(err) => return Err(err),
+            };
+
+        Ok(Self {
+            restore,
+            _thread: PhantomData,
+        })
+    }
+}
+
+impl Drop for BackgroundPriority {
+    fn drop(&mut self) {
+        if self.restore {
+            unsafe {
+                SetThreadPriority(GetCurrentThread(), THREAD_MODE_BACKGROUND_END);
+            }
+        }
+    }
+}
+
+/// A guard that keeps an operation from timing out until it is dropped, created by
+/// [FetchData::keep_alive][crate::ticket::FetchData::keep_alive].
+///
+/// The guard could be sent to another thread along with the ticket. Dropping it waits for the
+/// background thread to exit, which happens immediately.
+#[derive(Debug)]
+pub struct KeepAliveGuard {
+    shutdown: Option<Sender<()>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl KeepAliveGuard {
+    fn spawn(connection_key: RawConnectionKey, transfer_key: RawTransferKey) -> Self {
+        let (shutdown, receiver) = mpsc::channel();
+
+        let thread = thread::spawn(move || {
+            while let Err(RecvTimeoutError::Timeout) = receiver.recv_timeout(KEEP_ALIVE_INTERVAL) {
+                // reporting progress is the only way to reset the timeout without performing an
+                // operation, it fails once the operation is completed
+                if unsafe {
+                    CfReportProviderProgress(CF_CONNECTION_KEY(connection_key), transfer_key, 0, 0)
+                }
+                .is_err()
+                {
+                    break;
+                }
+            }
+        });
+
+        Self {
+            shutdown: Some(shutdown),
+            thread: Some(thread),
+        }
+    }
+}
+
+impl Drop for KeepAliveGuard {
+    fn drop(&mut self) {
+        // dropping the sender wakes the thread up
+        self.shutdown.take();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
 /// A ticket for the [SyncFilter::dehydrate][crate::SyncFilter::dehydrate] callback.
 #[derive(Debug)]
 pub struct Dehydrate {
     connection_key: RawConnectionKey,
     transfer_key: RawTransferKey,
+    _tracked: Option<Tracked>,
 }
 
 impl Dehydrate {
@@ -111,9 +696,16 @@ impl Dehydrate {
         Self {
             connection_key,
             transfer_key,
+            _tracked: None,
         }
     }
 
+    // removes the operation from the inflight registry when the ticket is dropped
+    pub(crate) fn tracked(mut self, tracked: Tracked) -> Self {
+        self._tracked = Some(tracked);
+        self
+    }
+
     /// Confirms dehydration of the file.
     pub fn pass(&self) -> core::Result<()> {
         command::Dehydrate { blob: None }.execute(self.connection_key, self.transfer_key)
@@ -135,6 +727,7 @@ impl Dehydrate {
 pub struct Delete {
     connection_key: RawConnectionKey,
     transfer_key: RawTransferKey,
+    _tracked: Option<Tracked>,
 }
 
 impl Delete {
@@ -143,14 +736,34 @@ impl Delete {
         Self {
             connection_key,
             transfer_key,
+            _tracked: None,
         }
     }
 
+    // removes the operation from the inflight registry when the ticket is dropped
+    pub(crate) fn tracked(mut self, tracked: Tracked) -> Self {
+        self._tracked = Some(tracked);
+        self
+    }
+
     /// Confirms deletion of the file.
     pub fn pass(&self) -> core::Result<()> {
         command::Delete.execute(self.connection_key, self.transfer_key)
     }
 
+    /// Runs the closure, then confirms deletion of the file if it succeeded or fails the callback
+    /// with the returned error otherwise.
+    ///
+    /// This is useful for deleting the file/directory from the remote before confirming. Use
+    /// [Delete::is_directory][crate::info::Delete::is_directory] to determine whether to delete
+    /// recursively.
+    pub fn pass_after(&self, f: impl FnOnce() -> Result<(), CloudErrorKind>) -> core::Result<()> {
+        match f() {
+            Ok(()) => self.pass(),
+            Err(error_kind) => self.fail(error_kind),
+        }
+    }
+
     /// Fail the callback with the specified error.
     pub fn fail(&self, error_kind: CloudErrorKind) -> core::Result<()> {
         command::Delete::fail(self.connection_key, self.transfer_key, error_kind)
@@ -162,6 +775,7 @@ impl Delete {
 pub struct Rename {
     connection_key: RawConnectionKey,
     transfer_key: RawTransferKey,
+    _tracked: Option<Tracked>,
 }
 
 impl Rename {
@@ -170,9 +784,16 @@ impl Rename {
         Self {
             connection_key,
             transfer_key,
+            _tracked: None,
         }
     }
 
+    // removes the operation from the inflight registry when the ticket is dropped
+    pub(crate) fn tracked(mut self, tracked: Tracked) -> Self {
+        self._tracked = Some(tracked);
+        self
+    }
+
     /// Confirms the rename/move of a file.
     pub fn pass(&self) -> core::Result<()> {
         command::Rename.execute(self.connection_key, self.transfer_key)
@@ -183,3 +804,364 @@ impl Rename {
         command::Rename::fail(self.connection_key, self.transfer_key, error_kind)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        env, fs,
+        fs::File,
+        io::Cursor,
+        os::windows::fs::FileExt as _,
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            Mutex,
+        },
+    };
+
+    use widestring::U16String;
+
+    use super::*;
+    use crate::{
+        ext::FileExt, HydrationPolicy, HydrationType, Registration, Request, SecurityId, Session,
+        SyncFilter, SyncRootIdBuilder,
+    };
+
+    #[test]
+    fn throttle_none_never_delays() {
+        let mut throttle = ThrottlePolicy::None;
+        assert_eq!(throttle.delay(1 << 30, Duration::ZERO), Duration::ZERO);
+    }
+
+    #[test]
+    fn throttle_rate_waits_for_the_expected_time() {
+        let mut throttle = ThrottlePolicy::BytesPerSecond(NonZeroU64::new(1000).unwrap());
+        assert_eq!(throttle.delay(2000, Duration::ZERO), Duration::from_secs(2));
+        assert_eq!(
+            throttle.delay(2000, Duration::from_millis(500)),
+            Duration::from_millis(1500)
+        );
+    }
+
+    #[test]
+    fn throttle_rate_does_not_delay_a_slow_transfer() {
+        let mut throttle = ThrottlePolicy::BytesPerSecond(NonZeroU64::new(1000).unwrap());
+        assert_eq!(throttle.delay(1000, Duration::from_secs(3)), Duration::ZERO);
+    }
+
+    #[test]
+    fn throttle_callback_receives_the_transferred_bytes() {
+        let (sender, receiver) = mpsc::channel();
+        let mut throttle = ThrottlePolicy::Callback(Box::new(move |transferred| {
+            sender.send(transferred).unwrap();
+            Duration::from_millis(transferred)
+        }));
+        assert_eq!(
+            throttle.delay(10, Duration::from_secs(1)),
+            Duration::from_millis(10)
+        );
+        assert_eq!(
+            throttle.delay(25, Duration::ZERO),
+            Duration::from_millis(25)
+        );
+        assert_eq!(receiver.try_iter().collect::<Vec<_>>(), [10, 25]);
+    }
+
+    #[test]
+    fn restart_hydration_rejects_oversized_blob() {
+        let ticket = FetchData::new(0, 0);
+        let blob = vec![0; CloudFilters::CF_PLACEHOLDER_MAX_FILE_IDENTITY_LENGTH as usize + 1];
+        let err = ticket
+            .restart_hydration(false, None, Some(&blob))
+            .unwrap_err();
+        assert_eq!(err.code(), E_INVALIDARG);
+    }
+
+    struct RestartFilter {
+        data: Vec<u8>,
+        restarted: AtomicBool,
+    }
+
+    impl SyncFilter for RestartFilter {
+        fn fetch_data(&self, _request: Request, ticket: FetchData, info: info::FetchData) {
+            // the first hydration discovers the placeholder is stale and updates its blob
+            if !self.restarted.swap(true, Ordering::SeqCst) {
+                #[allow(unused_must_use)]
+                {
+                    ticket.restart_hydration(false, None, Some(b"updated"));
+                }
+            }
+
+            #[allow(unused_must_use)]
+            {
+                ticket.transfer_from(&info, RangeStrategy::RequiredOnly, Cursor::new(&self.data));
+            }
+        }
+    }
+
+    #[test]
+    fn restart_hydration_updates_blob() {
+        let path = env::temp_dir().join("wincs-restart-hydration");
+        fs::create_dir_all(&path).unwrap();
+
+        let sync_root_id = SyncRootIdBuilder::new(U16String::from_str("wincs-test"))
+            .user_security_id(SecurityId::current_user().unwrap())
+            .account_name(U16String::from_str("restart-hydration"))
+            .build();
+        let _registration = Registration::from_sync_root_id(&sync_root_id)
+            .hydration_type(HydrationType::Full)
+            .register_scoped(&path)
+            .unwrap();
+
+        let data = b"restarted hydration".to_vec();
+
+        // deletes are not approved by the filter, so leftovers are removed before connecting
+        let file = path.join("file.txt");
+        let _ = fs::remove_file(&file);
+        let connection = Session::new()
+            .index(false)
+            .connect(
+                &path,
+                RestartFilter {
+                    data: data.clone(),
+                    restarted: AtomicBool::new(false),
+                },
+            )
+            .unwrap();
+
+        PlaceholderFile::new("file.txt")
+            .unwrap()
+            .metadata(Metadata::file().size(data.len() as u64))
+            .blob(b"stale".to_vec())
+            .mark_sync()
+            .create(&path)
+            .unwrap();
+
+        assert_eq!(fs::read(&file).unwrap(), data);
+        assert!(connection.filter().restarted.load(Ordering::SeqCst));
+        assert_eq!(
+            File::open(&file)
+                .unwrap()
+                .placeholder_info()
+                .unwrap()
+                .blob(),
+            b"updated"
+        );
+
+        connection.disconnect().unwrap();
+        fs::remove_file(&file).unwrap();
+    }
+
+    #[derive(Default)]
+    struct NamedFilter {
+        results: Mutex<Vec<(OsString, bool)>>,
+    }
+
+    impl SyncFilter for NamedFilter {
+        fn fetch_placeholders(
+            &self,
+            _request: Request,
+            ticket: FetchPlaceholders,
+            _info: info::FetchPlaceholders,
+        ) {
+            let mut placeholders =
+                ["first.txt", "duplicate.txt", "duplicate.txt", "last.txt"].map(|name| {
+                    PlaceholderFile::new(name)
+                        .unwrap()
+                        .metadata(Metadata::file())
+                        .mark_sync()
+                });
+            if let Ok(results) = ticket.pass_with_placeholder_named(&mut placeholders) {
+                *self.results.lock().unwrap() = results
+                    .into_iter()
+                    .map(|(name, result)| (name, result.is_ok()))
+                    .collect();
+            }
+        }
+    }
+
+    #[test]
+    fn pass_with_placeholder_named_pairs_results() {
+        let path = env::temp_dir().join("wincs-placeholders-named");
+        fs::create_dir_all(&path).unwrap();
+
+        let sync_root_id = SyncRootIdBuilder::new(U16String::from_str("wincs-test"))
+            .user_security_id(SecurityId::current_user().unwrap())
+            .account_name(U16String::from_str("placeholders-named"))
+            .build();
+        let _registration = Registration::from_sync_root_id(&sync_root_id)
+            .register_scoped(&path)
+            .unwrap();
+
+        // deletes are not approved by the filter, so leftovers are removed before connecting
+        let directory = path.join("named");
+        let _ = fs::remove_dir_all(&directory);
+        PlaceholderFile::new("named")
+            .unwrap()
+            .metadata(Metadata::directory())
+            .mark_sync()
+            .create(&path)
+            .unwrap();
+        let connection = Session::new()
+            .index(false)
+            .connect(&path, NamedFilter::default())
+            .unwrap();
+
+        // enumerating the directory populates it through the filter
+        fs::read_dir(&directory).unwrap().for_each(drop);
+        assert_eq!(
+            *connection.filter().results.lock().unwrap(),
+            [
+                (OsString::from("first.txt"), true),
+                (OsString::from("duplicate.txt"), true),
+                (OsString::from("duplicate.txt"), false),
+                (OsString::from("last.txt"), true),
+            ]
+        );
+
+        connection.disconnect().unwrap();
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[derive(Default)]
+    struct ValidateFilter {
+        results: Mutex<Vec<bool>>,
+    }
+
+    impl SyncFilter for ValidateFilter {
+        fn fetch_data(&self, _request: Request, ticket: FetchData, info: info::FetchData) {
+            #[allow(unused_must_use)]
+            {
+                ticket.transfer_from(&info, RangeStrategy::RequiredOnly, Cursor::new(b"content"));
+            }
+        }
+
+        fn validate_data(&self, request: Request, ticket: ValidateData, info: info::ValidateData) {
+            // the blob of each placeholder holds the content it is expected to have
+            let expected = request.file_blob();
+            if let Ok(valid) = ticket.validate_with(&info, |chunk, offset| {
+                expected.get(offset as usize..offset as usize + chunk.len()) == Some(chunk)
+            }) {
+                self.results.lock().unwrap().push(valid);
+            }
+        }
+    }
+
+    #[test]
+    fn validate_with_rejects_corrupted_chunk() {
+        let path = env::temp_dir().join("wincs-validate-with");
+        fs::create_dir_all(&path).unwrap();
+
+        let sync_root_id = SyncRootIdBuilder::new(U16String::from_str("wincs-test"))
+            .user_security_id(SecurityId::current_user().unwrap())
+            .account_name(U16String::from_str("validate-with"))
+            .build();
+        let _registration = Registration::from_sync_root_id(&sync_root_id)
+            .hydration_type(HydrationType::Full)
+            .hydration_policy(HydrationPolicy::default().require_validation())
+            .register_scoped(&path)
+            .unwrap();
+
+        // deletes are not approved by the filter, so leftovers are removed before connecting
+        let matching = path.join("matching.txt");
+        let corrupted = path.join("corrupted.txt");
+        let _ = fs::remove_file(&matching);
+        let _ = fs::remove_file(&corrupted);
+        let connection = Session::new()
+            .index(false)
+            .connect(&path, ValidateFilter::default())
+            .unwrap();
+
+        for (name, expected) in [("matching.txt", b"content"), ("corrupted.txt", b"c0ntent")] {
+            PlaceholderFile::new(name)
+                .unwrap()
+                .metadata(Metadata::file().size(expected.len() as u64))
+                .blob(expected.to_vec())
+                .mark_sync()
+                .create(&path)
+                .unwrap();
+        }
+
+        assert_eq!(fs::read(&matching).unwrap(), b"content");
+        assert!(fs::read(&corrupted).is_err());
+        assert_eq!(*connection.filter().results.lock().unwrap(), [true, false]);
+
+        connection.disconnect().unwrap();
+        fs::remove_file(&matching).unwrap();
+        fs::remove_file(&corrupted).unwrap();
+    }
+
+    struct ProgressiveFilter {
+        data: Vec<u8>,
+        ranges: Mutex<Vec<Range<u64>>>,
+    }
+
+    impl SyncFilter for ProgressiveFilter {
+        fn fetch_data(&self, _request: Request, ticket: FetchData, info: info::FetchData) {
+            // the source starts at the required range, as would the body of an HTTP range request
+            let range = info.required_file_range();
+            self.ranges.lock().unwrap().push(range.clone());
+            #[allow(unused_must_use)]
+            {
+                ticket.transfer_required_from(&info, &self.data[range.start as usize..]);
+            }
+        }
+    }
+
+    #[test]
+    fn transfer_required_from_serves_disjoint_ranges() {
+        let path = env::temp_dir().join("wincs-transfer-required");
+        fs::create_dir_all(&path).unwrap();
+
+        let sync_root_id = SyncRootIdBuilder::new(U16String::from_str("wincs-test"))
+            .user_security_id(SecurityId::current_user().unwrap())
+            .account_name(U16String::from_str("transfer-required"))
+            .build();
+        let _registration = Registration::from_sync_root_id(&sync_root_id)
+            .hydration_type(HydrationType::Progressive)
+            .register_scoped(&path)
+            .unwrap();
+
+        let data = (0..1 << 20).map(|i| (i % 251) as u8).collect::<Vec<_>>();
+
+        // deletes are not approved by the filter, so leftovers are removed before connecting
+        let file = path.join("file.bin");
+        let _ = fs::remove_file(&file);
+        let connection = Session::new()
+            .index(false)
+            .connect(
+                &path,
+                ProgressiveFilter {
+                    data: data.clone(),
+                    ranges: Mutex::default(),
+                },
+            )
+            .unwrap();
+
+        PlaceholderFile::new("file.bin")
+            .unwrap()
+            .metadata(Metadata::file().size(data.len() as u64))
+            .mark_sync()
+            .create(&path)
+            .unwrap();
+
+        let handle = File::open(&file).unwrap();
+        let mut buffer = [0; 16];
+        for offset in [0, 1 << 19] {
+            handle.seek_read(&mut buffer, offset).unwrap();
+            assert_eq!(
+                buffer,
+                data[offset as usize..offset as usize + buffer.len()]
+            );
+        }
+        drop(handle);
+
+        // the operating system could request further ranges in the background
+        let ranges = connection.filter().ranges.lock().unwrap().clone();
+        assert!(ranges.len() >= 2);
+        assert!(ranges[0].contains(&0));
+        assert!(ranges[0].end <= ranges[1].start);
+
+        connection.disconnect().unwrap();
+        fs::remove_file(&file).unwrap();
+    }
+}
@@ -1,28 +1,62 @@
+mod buffer_pool;
 /// Contains low-level structs for directly executing Cloud Filter operations.
 ///
 /// The [command][crate::command] API is exposed through various higher-level structs, like
 /// [Request][crate::Request] and [Placeholder][crate::Placeholder]. Thus, it is not necessary to
 /// create and call these structs manually unless you need more granular access.
 pub mod command;
+mod correlation_vector;
 mod error;
 /// Contains traits extending common structs from the [std][std].
 pub mod ext;
+mod file_time;
 pub mod filter;
 pub mod placeholder;
 pub mod placeholder_file;
+/// Contains functions for querying the capabilities of the running version of Windows.
+pub mod platform;
+/// Re-exports the extension traits of the crate so they could be brought into scope with a single
+/// `use wincs::prelude::*;`.
+///
+/// ```no_run
+/// use std::{fs::File, path::Path};
+///
+/// use wincs::prelude::*;
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let path = Path::new("C:\\SyncRoot\\file.txt");
+/// if path.in_sync_root() && !path.is_dehydrated()? {
+///     let info = File::open(path)?.placeholder_info()?;
+///     println!("{} bytes are on disk", info.on_disk_data_size());
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub mod prelude;
 pub mod request;
 pub mod root;
+/// Contains an in-memory [SyncFilter][crate::SyncFilter] for testing integrations against a real
+/// sync root.
+#[cfg(feature = "testing")]
+pub mod testing;
 pub mod usn;
 mod utility;
+mod walk;
+mod wide_path;
 
+pub use buffer_pool::{BufferPool, VecPool};
+pub use correlation_vector::CorrelationVector;
 pub use error::CloudErrorKind;
+pub use file_time::FileTime;
 pub use filter::{info, ticket, SyncFilter};
 pub use placeholder::{Placeholder, UpdateOptions};
 pub use placeholder_file::{BatchCreate, Metadata, PlaceholderFile};
-pub use request::{Process, Request};
+pub use request::{Process, Request, RequestSnapshot};
 pub use root::{
-    active_roots, is_supported, Connection, HydrationPolicy, HydrationType, PopulationType,
-    ProtectionMode, Registration, SecurityId, Session, SupportedAttributes, SyncRootId,
-    SyncRootIdBuilder,
+    active_roots, deindex_path, is_supported, unregister_provider, Connection, ConnectionHandle,
+    HydrationPolicy, HydrationType, InvalidName, PopulationType, ProtectionMode, Registration,
+    SecurityId, Session, ShellHandlers, SupportedAttributes, SyncRootId, SyncRootIdBuilder,
+    SyncRootRegistration,
 };
 pub use usn::Usn;
+pub use wide_path::WidePath;
@@ -1,18 +1,26 @@
 use std::{
+    fs::{File, OpenOptions},
     io::{self, Seek, SeekFrom},
     mem::ManuallyDrop,
-    ops::Range,
+    ops::{Range, RangeBounds},
+    os::windows::{fs::OpenOptionsExt, io::AsRawHandle},
     path::{Path, PathBuf},
     ptr,
+    sync::Arc,
 };
 
 use widestring::U16CString;
 use windows::{
     core::{self, GUID},
     Win32::{
+        Foundation::HANDLE,
         Storage::{
-            CloudFilters::{self, CfReportProviderProgress, CF_CONNECTION_KEY},
+            CloudFilters::{
+                self, CfGetTransferKey, CfReleaseTransferKey, CfReportProviderProgress,
+                CF_CONNECTION_KEY,
+            },
             EnhancedStorage,
+            FileSystem::FILE_FLAG_BACKUP_SEMANTICS,
         },
         System::{
             Com::StructuredStorage::{
@@ -24,6 +32,7 @@ use windows::{
             self, IShellItem2,
             PropertiesSystem::{
                 self, IPropertyStore, InitPropVariantFromUInt64Vector, PROPERTYKEY,
+                SYNC_TRANSFER_STATUS,
             },
             SHChangeNotify, SHCreateItemFromParsingName,
         },
@@ -32,8 +41,13 @@ use windows::{
 
 use crate::{
     command::{Command, Read, Update, Validate, Write},
+    ext::FileExt,
     placeholder_file::Metadata,
+    platform,
     request::{RawConnectionKey, RawTransferKey},
+    usn::Usn,
+    utility::io_to_core,
+    walk,
 };
 
 // secret PKEY
@@ -57,6 +71,8 @@ pub struct Placeholder {
     // TODO: how does file size behave when writing past the last recorded file size?
     file_size: u64,
     position: u64,
+    // only set if the transfer key was acquired from a handle, rather than a callback
+    owned: Option<Arc<OwnedTransferKey>>,
 }
 
 impl Placeholder {
@@ -72,25 +88,47 @@ impl Placeholder {
             path,
             file_size,
             position: 0,
+            owned: None,
         }
     }
 
-    // TODO: this function is a bit of a pickle
-    // if I let the user pass in the transfer key, then I somehow need to get the file path to support set_progress
-    // if I allow getting a transfer key from a path, then I somehow need to let them specify read/write access (probably?)
+    /// Creates a [Placeholder][crate::Placeholder] for the placeholder at the given path, so
+    /// operations could be performed outside of a callback, e.g. from a sync loop.
+    ///
+    /// The placeholder is opened and a transfer key is acquired for it, both are released once
+    /// every clone of the [Placeholder][crate::Placeholder] is dropped. Opening the placeholder
+    /// does not hydrate it. Prefer
+    /// [Connection::placeholder][crate::Connection::placeholder], which ensures the path is
+    /// within the sync root.
     pub fn from_path<P: AsRef<Path>>(
-        _connection_key: RawConnectionKey,
-        _path: P,
+        connection_key: RawConnectionKey,
+        path: P,
     ) -> core::Result<Self> {
-        // let file = File::open(path).unwrap();
-        // let key = unsafe { CfGetTransferKey(HANDLE(file.as_raw_handle() as isize))?};
-        // OwnedTransferKey::new(key, file);
-
-        // Ok(Self {
-        //     connection_key,
+        let path = path.as_ref().to_owned();
+        let file = OpenOptions::new()
+            .read(true)
+            .custom_flags(FILE_FLAG_BACKUP_SEMANTICS.0)
+            .open(&path)
+            .map_err(io_to_core)?;
+        let file_size = file.metadata().map_err(io_to_core)?.len();
+        let transfer_key = unsafe { CfGetTransferKey(HANDLE(file.as_raw_handle() as isize)) }?;
+
+        Ok(Self {
+            owned: Some(Arc::new(OwnedTransferKey { file, transfer_key })),
+            ..Self::new(connection_key, transfer_key, path, file_size)
+        })
+    }
 
-        // })
-        todo!()
+    /// Hydrates the specified range of the placeholder.
+    ///
+    /// This is equivalent to [FileExt::hydrate][crate::ext::FileExt::hydrate] and must not be
+    /// called from within the [SyncFilter::fetch_data][crate::SyncFilter::fetch_data] callback of
+    /// the same placeholder, as the hydration would wait on itself.
+    pub fn hydrate(&self, range: impl RangeBounds<u64>) -> core::Result<()> {
+        match &self.owned {
+            Some(owned) => owned.file.hydrate(range),
+            None => File::open(&self.path).map_err(io_to_core)?.hydrate(range),
+        }
     }
 
     /// Validates the data range in the placeholder file is valid.
@@ -117,6 +155,19 @@ impl Placeholder {
         self.update(UpdateOptions::new().mark_sync())
     }
 
+    /// Marks the placeholder as not in sync, so the file explorer shows it as pending a sync,
+    /// returning the resulting [Usn][crate::Usn].
+    ///
+    /// This is the reverse of [Placeholder::mark_sync][crate::Placeholder::mark_sync] and is
+    /// equivalent to calling [FileExt::mark_unsync][crate::ext::FileExt::mark_unsync], which sets
+    /// the in-sync state via `CfSetInSyncState`. If the passed [Usn][crate::Usn] is outdated, the
+    /// call will fail, a [Usn][crate::Usn] of 0 skips the check.
+    pub fn mark_pending(&self, usn: Usn) -> core::Result<Usn> {
+        walk::open_write(&self.path)
+            .map_err(io_to_core)?
+            .mark_unsync(usn)
+    }
+
     /// Shortcut for calling [Placeholder::update][crate::Placeholder::update] with
     /// [UpdateOptions::metadata][crate::UpdateOptions::metadata].
     pub fn set_metadata(&self, metadata: Metadata) -> core::Result<()> {
@@ -142,39 +193,108 @@ impl Placeholder {
                 total as i64,
                 completed as i64,
             )?;
+        }
 
-            let item: IShellItem2 = SHCreateItemFromParsingName(self.path.as_os_str(), None)?;
-            let store: IPropertyStore = item.GetPropertyStore(
-                PropertiesSystem::GPS_READWRITE | PropertiesSystem::GPS_VOLATILEPROPERTIESONLY,
-            )?;
+        set_transfer_status(
+            &self.path,
+            if completed < total {
+                PropertiesSystem::STS_TRANSFERRING
+            } else {
+                PropertiesSystem::STS_NONE
+            },
+            Some((completed, total)),
+        )
+    }
+
+    /// Marks the placeholder as having failed to sync, displaying an error icon next to the file
+    /// in the file explorer.
+    ///
+    /// Only a generic error badge is displayed, the transfer status carries no reason, so the file
+    /// explorer shows no text describing the error. The reason should be surfaced to the user by
+    /// other means, e.g. a notification.
+    ///
+    /// The error is cleared by the next call to
+    /// [Placeholder::set_progress][crate::Placeholder::set_progress]. The transfer status is a
+    /// shell property, so the shell must be available within the current process (e.g. not a
+    /// service running in session 0).
+    pub fn report_error(&self) -> core::Result<()> {
+        set_transfer_status(&self.path, PropertiesSystem::STS_HASERROR, None)
+    }
 
+    /// Shows or clears the syncing indicator next to the file in the file explorer, e.g. while the
+    /// file is being uploaded.
+    ///
+    /// Unlike [Placeholder::set_progress][crate::Placeholder::set_progress], no progress is
+    /// displayed. The in-sync state of the placeholder is left as is, call
+    /// [Placeholder::mark_sync][crate::Placeholder::mark_sync] once the upload completes. The same
+    /// shell requirements as [Placeholder::report_error][crate::Placeholder::report_error] apply.
+    pub fn set_syncing(&self, syncing: bool) -> core::Result<()> {
+        set_transfer_status(
+            &self.path,
+            if syncing {
+                PropertiesSystem::STS_TRANSFERRING
+            } else {
+                PropertiesSystem::STS_NONE
+            },
+            None,
+        )
+    }
+}
+
+// keeps the handle a transfer key was acquired from open, releasing the key when dropped
+#[derive(Debug)]
+struct OwnedTransferKey {
+    file: File,
+    transfer_key: RawTransferKey,
+}
+
+impl Drop for OwnedTransferKey {
+    fn drop(&mut self) {
+        unsafe {
+            CfReleaseTransferKey(
+                HANDLE(self.file.as_raw_handle() as isize),
+                &mut self.transfer_key,
+            )
+        }
+    }
+}
+
+// sets the transfer status (and optionally the progress) displayed by the shell for the item
+fn set_transfer_status(
+    path: &Path,
+    status: SYNC_TRANSFER_STATUS,
+    progress: Option<(u64, u64)>,
+) -> core::Result<()> {
+    unsafe {
+        let item: IShellItem2 = SHCreateItemFromParsingName(path.as_os_str(), None)?;
+        let store: IPropertyStore = item.GetPropertyStore(
+            PropertiesSystem::GPS_READWRITE | PropertiesSystem::GPS_VOLATILEPROPERTIESONLY,
+        )?;
+
+        if let Some((completed, total)) = progress {
             let progress = InitPropVariantFromUInt64Vector(&mut [completed, total] as *mut _, 2)?;
             store.SetValue(
                 &STORAGE_PROVIDER_TRANSFER_PROGRESS as *const _,
                 &progress as *const _,
             )?;
+        }
 
-            let status = InitPropVariantFromUInt32(if completed < total {
-                PropertiesSystem::STS_TRANSFERRING.0
-            } else {
-                PropertiesSystem::STS_NONE.0
-            });
-            store.SetValue(
-                &EnhancedStorage::PKEY_SyncTransferStatus as *const _,
-                &status as *const _,
-            )?;
+        let status = InitPropVariantFromUInt32(status.0);
+        store.SetValue(
+            &EnhancedStorage::PKEY_SyncTransferStatus as *const _,
+            &status as *const _,
+        )?;
 
-            store.Commit()?;
+        store.Commit()?;
 
-            SHChangeNotify(
-                Shell::SHCNE_UPDATEITEM,
-                Shell::SHCNF_PATHW,
-                U16CString::from_os_str_unchecked(self.path.as_os_str()).as_ptr() as *const _,
-                ptr::null_mut(),
-            );
+        SHChangeNotify(
+            Shell::SHCNE_UPDATEITEM,
+            Shell::SHCNF_PATHW,
+            U16CString::from_os_str_unchecked(path.as_os_str()).as_ptr() as *const _,
+            ptr::null_mut(),
+        );
 
-            Ok(())
-        }
+        Ok(())
     }
 }
 
@@ -208,8 +328,9 @@ impl io::Read for Placeholder {
 impl io::Write for Placeholder {
     /// Write data to a placeholder.
     ///
-    /// The buffer passed must be 4KiB in length or end on the logical file size. Unfortunately,
-    /// this is a restriction of the operating system. Read
+    /// The length of the buffer must be a multiple of
+    /// [transfer_alignment][crate::platform::transfer_alignment] or end on the logical file size.
+    /// Unfortunately, this is a restriction of the operating system. Read
     /// [here](https://github.com/ok-nick/wincs/issues/3) for a convenient abstraction.
     ///
     /// The bytes returned will ALWAYS be the length of the buffer passed in. The operating system
@@ -220,8 +341,9 @@ impl io::Write for Placeholder {
     // TODO: confirm the abovementioned is true
     fn write(&mut self, buffer: &[u8]) -> io::Result<usize> {
         assert!(
-            buffer.len() % 4096 == 0 || self.position + buffer.len() as u64 >= self.file_size,
-            "the length of the buffer must be 4KiB aligned or ending on the logical file size"
+            buffer.len() % platform::transfer_alignment() == 0
+                || self.position + buffer.len() as u64 >= self.file_size,
+            "the length of the buffer must be aligned or ending on the logical file size"
         );
 
         let result = Write {
@@ -327,3 +449,100 @@ fn InitPropVariantFromUInt32(ulVal: u32) -> PROPVARIANT {
         },
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs};
+
+    use widestring::U16String;
+
+    use super::*;
+    use crate::{
+        PlaceholderFile, Registration, SecurityId, Session, SyncFilter, SyncRootIdBuilder,
+    };
+
+    struct EmptyFilter;
+
+    impl SyncFilter for EmptyFilter {}
+
+    #[test]
+    fn report_error_smoke_test() {
+        let path = env::temp_dir().join("wincs-report-error");
+        fs::create_dir_all(&path).unwrap();
+
+        let sync_root_id = SyncRootIdBuilder::new(U16String::from_str("wincs-test"))
+            .user_security_id(SecurityId::current_user().unwrap())
+            .account_name(U16String::from_str("report-error"))
+            .build();
+        let _registration = Registration::from_sync_root_id(&sync_root_id)
+            .register_scoped(&path)
+            .unwrap();
+
+        // deletes are not approved by the filter, so leftovers are removed before connecting
+        let file = path.join("error.txt");
+        let _ = fs::remove_file(&file);
+        let connection = Session::new()
+            .index(false)
+            .connect(&path, EmptyFilter)
+            .unwrap();
+
+        PlaceholderFile::new("error.txt")
+            .unwrap()
+            .metadata(Metadata::file())
+            .mark_sync()
+            .create(&path)
+            .unwrap();
+
+        // the shell could be unavailable, e.g. in session 0, so only the absence of a panic is
+        // checked
+        let placeholder = connection.placeholder("error.txt").unwrap();
+        let _ = placeholder.report_error();
+        drop(placeholder);
+
+        connection.disconnect().unwrap();
+        fs::remove_file(&file).unwrap();
+    }
+
+    #[test]
+    fn mark_pending_clears_sync_state() {
+        let path = env::temp_dir().join("wincs-mark-pending");
+        fs::create_dir_all(&path).unwrap();
+
+        let sync_root_id = SyncRootIdBuilder::new(U16String::from_str("wincs-test"))
+            .user_security_id(SecurityId::current_user().unwrap())
+            .account_name(U16String::from_str("mark-pending"))
+            .build();
+        let _registration = Registration::from_sync_root_id(&sync_root_id)
+            .register_scoped(&path)
+            .unwrap();
+
+        // deletes are not approved by the filter, so leftovers are removed before connecting
+        let file = path.join("pending.txt");
+        let _ = fs::remove_file(&file);
+        let connection = Session::new()
+            .index(false)
+            .connect(&path, EmptyFilter)
+            .unwrap();
+
+        let usn = PlaceholderFile::new("pending.txt")
+            .unwrap()
+            .metadata(Metadata::file())
+            .mark_sync()
+            .create(&path)
+            .unwrap();
+
+        let placeholder = connection.placeholder("pending.txt").unwrap();
+        let pending_usn = placeholder.mark_pending(usn).unwrap();
+        drop(placeholder);
+
+        assert!(pending_usn > usn);
+        assert!(!walk::open_attributes(&file)
+            .unwrap()
+            .placeholder_info()
+            .unwrap()
+            .is_synced());
+
+        connection.disconnect().unwrap();
+        fs::remove_file(&file).unwrap();
+    }
+}
@@ -1,42 +1,89 @@
-use std::{fs, os::windows::prelude::MetadataExt, path::Path, ptr, slice};
+use std::{
+    fs::{self, OpenOptions},
+    io::{self, Read},
+    mem,
+    os::windows::{io::AsRawHandle, prelude::MetadataExt},
+    path::{Path, PathBuf},
+    ptr, slice,
+};
 
-use widestring::U16CString;
+use widestring::{U16CStr, U16CString};
 use windows::{
     core::{self, PCWSTR},
     Win32::{
-        Foundation,
+        Foundation::{self, HANDLE},
         Storage::{
             CloudFilters::{
                 self, CfCreatePlaceholders, CF_FS_METADATA, CF_PLACEHOLDER_CREATE_INFO,
             },
-            FileSystem::{FILE_ATTRIBUTE_DIRECTORY, FILE_ATTRIBUTE_NORMAL, FILE_BASIC_INFO},
+            FileSystem::{
+                self, SetFileInformationByHandle, FILE_ATTRIBUTE_DIRECTORY, FILE_ATTRIBUTE_NORMAL,
+                FILE_BASIC_INFO,
+            },
         },
     },
 };
 
-use crate::usn::Usn;
+use crate::{
+    ext::{ConvertOptions, FileExt, PinOptions, PinState},
+    file_time::FileTime,
+    usn::Usn,
+    utility::io_to_core,
+    walk,
+    wide_path::WidePath,
+};
 
 // TODO: this struct could probably have a better name to represent files/dirs
 /// A builder for creating new placeholder files/directories.
+///
+/// Every `CF_PLACEHOLDER_CREATE_FLAG` is exposed through a builder method:
+/// * `DISABLE_ON_DEMAND_POPULATION`: [PlaceholderFile::has_no_children][crate::PlaceholderFile::has_no_children]
+/// * `MARK_IN_SYNC`: [PlaceholderFile::mark_sync][crate::PlaceholderFile::mark_sync]
+/// * `SUPERSEDE`: [PlaceholderFile::overwrite][crate::PlaceholderFile::overwrite]
+/// * `ALWAYS_FULL`: [PlaceholderFile::block_dehydration][crate::PlaceholderFile::block_dehydration]
 #[repr(C)]
 #[derive(Debug)]
 pub struct PlaceholderFile(CF_PLACEHOLDER_CREATE_INFO);
 
 impl PlaceholderFile {
     /// Creates a new [PlaceholderFile][crate::PlaceholderFile].
-    pub fn new(relative_path: impl AsRef<Path>) -> Self {
+    ///
+    /// Returns `E_INVALIDARG` if the path contains a null character.
+    pub fn new(relative_path: impl AsRef<Path>) -> core::Result<Self> {
+        WidePath::try_from(relative_path.as_ref()).map(Self::from_wide)
+    }
+
+    /// Creates a new [PlaceholderFile][crate::PlaceholderFile] from a path that is already
+    /// encoded as a wide string, avoiding the conversion performed by
+    /// [PlaceholderFile::new][crate::PlaceholderFile::new].
+    pub fn from_wide(relative_path: impl Into<WidePath>) -> Self {
         Self(CF_PLACEHOLDER_CREATE_INFO {
-            RelativeFileName: PCWSTR(
-                U16CString::from_os_str(relative_path.as_ref())
-                    .unwrap()
-                    .into_raw(),
-            ),
+            RelativeFileName: PCWSTR(relative_path.into().into_inner().into_raw()),
             Flags: CloudFilters::CF_PLACEHOLDER_CREATE_FLAG_NONE,
             Result: Foundation::S_OK,
             ..Default::default()
         })
     }
 
+    /// Creates a new [PlaceholderFile][crate::PlaceholderFile] named after the existing file or
+    /// directory at the given path, with its timestamps, attributes, and size copied over.
+    ///
+    /// The placeholder should be created within the same parent directory, e.g. through
+    /// [PlaceholderFile::create][crate::PlaceholderFile::create] with
+    /// [PlaceholderFile::overwrite][crate::PlaceholderFile::overwrite].
+    pub fn from_existing(path: impl AsRef<Path>) -> core::Result<Self> {
+        let path = path.as_ref();
+        let metadata = fs::metadata(path).map_err(io_to_core)?;
+        let file_name = path.file_name().ok_or_else(|| {
+            core::Error::new(
+                Foundation::E_INVALIDARG,
+                "the path must end with a file name".into(),
+            )
+        })?;
+
+        Ok(Self::new(file_name)?.metadata(metadata.into()))
+    }
+
     /// Marks this [PlaceholderFile][crate::PlaceholderFile] as having no child placeholders on
     /// creation.
     ///
@@ -65,6 +112,10 @@ impl PlaceholderFile {
     }
 
     /// Whether or not to overwrite an existing placeholder.
+    ///
+    /// Without this flag, creating a placeholder that already exists will fail. When combined with
+    /// [PlaceholderFile::block_dehydration][crate::PlaceholderFile::block_dehydration], the
+    /// existing placeholder is replaced by one that can never be dehydrated.
     pub fn overwrite(mut self) -> Self {
         self.0.Flags |= CloudFilters::CF_PLACEHOLDER_CREATE_FLAG_SUPERSEDE;
         self
@@ -72,6 +123,11 @@ impl PlaceholderFile {
 
     /// Blocks this placeholder file from being dehydrated.
     ///
+    /// Placeholders are always created dehydrated, so this flag is meant to be followed by a
+    /// hydration, e.g. via [FileExt::hydrate][crate::ext::FileExt::hydrate], after which the
+    /// placeholder will stay fully present. Without this flag, a placeholder seeded with known
+    /// complete data could later be dehydrated by the platform.
+    ///
     /// This flag does not work on directories.
     pub fn block_dehydration(mut self) -> Self {
         self.0.Flags |= CloudFilters::CF_PLACEHOLDER_CREATE_FLAG_ALWAYS_FULL;
@@ -110,6 +166,20 @@ impl PlaceholderFile {
         self
     }
 
+    /// The path of the placeholder relative to the directory it is created in.
+    pub(crate) fn relative_path(&self) -> PathBuf {
+        // Safety: `self.0.RelativeFileName.0` is a valid pointer to a valid UTF-16 string
+        PathBuf::from(unsafe { U16CStr::from_ptr_str(self.0.RelativeFileName.0) }.to_os_string())
+    }
+
+    pub(crate) fn set_overwrite(&mut self, overwrite: bool) {
+        if overwrite {
+            self.0.Flags |= CloudFilters::CF_PLACEHOLDER_CREATE_FLAG_SUPERSEDE;
+        } else {
+            self.0.Flags &= !CloudFilters::CF_PLACEHOLDER_CREATE_FLAG_SUPERSEDE;
+        }
+    }
+
     /// Creates a placeholder file/directory on the file system.
     ///
     /// The value returned is the final [Usn][crate::Usn] after the placeholder is created.
@@ -120,7 +190,7 @@ impl PlaceholderFile {
     ///
     /// If you need to create placeholders from the [SyncFilter::fetch_placeholders][crate::SyncFilter::fetch_placeholders] callback, do not use this method. Instead, use
     /// [FetchPlaceholders::pass_with_placeholders][crate::ticket::FetchPlaceholders::pass_with_placeholders].
-    pub fn create<P: AsRef<Path>>(mut self, parent: impl AsRef<Path>) -> core::Result<Usn> {
+    pub fn create(mut self, parent: impl AsRef<Path>) -> core::Result<Usn> {
         unsafe {
             CfCreatePlaceholders(
                 parent.as_ref().as_os_str(),
@@ -133,6 +203,105 @@ impl PlaceholderFile {
 
         self.0.Result.ok().map(|_| self.0.CreateUsn as Usn)
     }
+
+    /// Creates a placeholder file/directory on the file system with the specified
+    /// [PinState][crate::ext::PinState], e.g. [PinState::Pinned][crate::ext::PinState::Pinned].
+    ///
+    /// The operating system does not accept a pin state on creation, so it is set with a
+    /// follow-up call to [FileExt::set_pin_state][crate::ext::FileExt::set_pin_state] right after
+    /// the placeholder is created. The placeholder is opened without read access, so it is not
+    /// hydrated by this call. If the pin state could not be set, the placeholder is left in place
+    /// and the error is returned.
+    ///
+    /// The value returned is the [Usn][crate::Usn] after the placeholder is created, before its
+    /// pin state is set.
+    pub fn create_with_pin_state(
+        self,
+        parent: impl AsRef<Path>,
+        state: PinState,
+    ) -> core::Result<Usn> {
+        let path = parent.as_ref().join(self.relative_path());
+        let usn = self.create(parent)?;
+        walk::open_write(&path)
+            .map_err(io_to_core)?
+            .set_pin_state(state, PinOptions::default())?;
+
+        Ok(usn)
+    }
+
+    /// Creates a placeholder file that is already fully present, with its content read from
+    /// `data`.
+    ///
+    /// Unlike [PlaceholderFile::create][crate::PlaceholderFile::create], which creates a
+    /// dehydrated stub whose content is later requested through
+    /// [SyncFilter::fetch_data][crate::SyncFilter::fetch_data], the content is written before the
+    /// file is converted to a placeholder, so reading it never invokes the callback. The
+    /// placeholder is always marked as synced.
+    ///
+    /// The value returned is the final [Usn][crate::Usn] after the placeholder is created. If the
+    /// file could not be converted, it is removed.
+    ///
+    /// This method does not work on directories.
+    pub fn create_present(
+        self,
+        parent: impl AsRef<Path>,
+        mut data: impl Read,
+    ) -> core::Result<Usn> {
+        let path = parent.as_ref().join(self.relative_path());
+        let overwrite = (self.0.Flags & CloudFilters::CF_PLACEHOLDER_CREATE_FLAG_SUPERSEDE).0 != 0;
+        let mut file = OpenOptions::new()
+            .write(true)
+            .create(overwrite)
+            .truncate(overwrite)
+            .create_new(!overwrite)
+            .open(&path)
+            .map_err(io_to_core)?;
+
+        let result = io::copy(&mut data, &mut file)
+            .map_err(io_to_core)
+            .and_then(|_| self.convert(&file));
+        if result.is_err() {
+            drop(file);
+            // the original error is more useful than a failure to clean up
+            #[allow(unused_must_use)]
+            {
+                fs::remove_file(&path);
+            }
+        }
+
+        result
+    }
+
+    // converts a file with its content already written to a placeholder matching this builder
+    fn convert(&self, file: &fs::File) -> core::Result<Usn> {
+        // zeroed fields are left unchanged, so the times are kept if they weren't specified
+        let basic_info = self.0.FsMetadata.BasicInfo;
+        unsafe {
+            SetFileInformationByHandle(
+                HANDLE(file.as_raw_handle() as isize),
+                FileSystem::FileBasicInfo,
+                &basic_info as *const _ as *const _,
+                mem::size_of::<FILE_BASIC_INFO>() as u32,
+            )
+        }
+        .ok()?;
+
+        let blob = (!self.0.FileIdentity.is_null()).then(|| unsafe {
+            slice::from_raw_parts(
+                self.0.FileIdentity as *const u8,
+                self.0.FileIdentityLength as usize,
+            )
+        });
+        let mut options = ConvertOptions::default().mark_sync();
+        if let Some(blob) = blob {
+            options = options.blob(blob);
+        }
+        if (self.0.Flags & CloudFilters::CF_PLACEHOLDER_CREATE_FLAG_ALWAYS_FULL).0 != 0 {
+            options = options.block_dehydration();
+        }
+
+        file.to_placeholder(options)
+    }
 }
 
 impl Drop for PlaceholderFile {
@@ -208,30 +377,33 @@ impl Metadata {
     }
 
     /// The time the file/directory was created.
-    pub fn creation_time(mut self, time: u64) -> Self {
-        self.0.BasicInfo.CreationTime = time as i64;
+    pub fn creation_time(mut self, time: FileTime) -> Self {
+        self.0.BasicInfo.CreationTime = time.ticks() as i64;
         self
     }
 
     /// The time the file/directory was last accessed.
-    pub fn last_access_time(mut self, time: u64) -> Self {
-        self.0.BasicInfo.LastAccessTime = time as i64;
+    pub fn last_access_time(mut self, time: FileTime) -> Self {
+        self.0.BasicInfo.LastAccessTime = time.ticks() as i64;
         self
     }
 
     /// The time the file/directory content was last written.
-    pub fn last_write_time(mut self, time: u64) -> Self {
-        self.0.BasicInfo.LastWriteTime = time as i64;
+    pub fn last_write_time(mut self, time: FileTime) -> Self {
+        self.0.BasicInfo.LastWriteTime = time.ticks() as i64;
         self
     }
 
     /// The time the file/directory content or metadata was changed.
-    pub fn change_time(mut self, time: u64) -> Self {
-        self.0.BasicInfo.ChangeTime = time as i64;
+    pub fn change_time(mut self, time: FileTime) -> Self {
+        self.0.BasicInfo.ChangeTime = time.ticks() as i64;
         self
     }
 
-    /// The size of the file's content.
+    /// The logical size of the file's content.
+    ///
+    /// There is no allocation size to specify, the space allocated on disk is managed by the
+    /// operating system and only grows as the placeholder is hydrated.
     pub fn size(mut self, size: u64) -> Self {
         self.0.FileSize = size as i64;
         self
@@ -259,3 +431,138 @@ impl From<fs::Metadata> for Metadata {
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        env,
+        sync::atomic::{AtomicUsize, Ordering},
+    };
+
+    use widestring::U16String;
+
+    use super::*;
+    use crate::{
+        filter::{info, ticket},
+        CloudErrorKind, Registration, Request, SecurityId, Session, SyncFilter, SyncRootIdBuilder,
+    };
+
+    #[derive(Default)]
+    struct CountingFilter {
+        fetches: AtomicUsize,
+    }
+
+    impl SyncFilter for CountingFilter {
+        fn fetch_data(&self, _request: Request, ticket: ticket::FetchData, _info: info::FetchData) {
+            self.fetches.fetch_add(1, Ordering::SeqCst);
+            #[allow(unused_must_use)]
+            {
+                ticket.fail(CloudErrorKind::Unsuccessful);
+            }
+        }
+    }
+
+    #[test]
+    fn new_rejects_nul() {
+        let err = PlaceholderFile::new("file\0.txt").unwrap_err();
+        assert_eq!(err.code(), Foundation::E_INVALIDARG);
+    }
+
+    #[test]
+    fn create_from_wide() {
+        let path = env::temp_dir().join("wincs-placeholder-wide");
+        fs::create_dir_all(&path).unwrap();
+
+        let sync_root_id = SyncRootIdBuilder::new(U16String::from_str("wincs-test"))
+            .user_security_id(SecurityId::current_user().unwrap())
+            .account_name(U16String::from_str("placeholder-wide"))
+            .build();
+        let _registration = Registration::from_sync_root_id(&sync_root_id)
+            .register_scoped(&path)
+            .unwrap();
+
+        let file = path.join("wide.txt");
+        let _ = fs::remove_file(&file);
+        PlaceholderFile::from_wide(U16CString::from_str("wide.txt").unwrap())
+            .metadata(Metadata::file())
+            .mark_sync()
+            .create(&path)
+            .unwrap();
+
+        assert!(walk::open_attributes(&file)
+            .unwrap()
+            .placeholder_info()
+            .unwrap()
+            .is_synced());
+        fs::remove_file(&file).unwrap();
+    }
+
+    #[test]
+    fn create_present_never_fetches() {
+        let path = env::temp_dir().join("wincs-placeholder-present");
+        fs::create_dir_all(&path).unwrap();
+
+        let sync_root_id = SyncRootIdBuilder::new(U16String::from_str("wincs-test"))
+            .user_security_id(SecurityId::current_user().unwrap())
+            .account_name(U16String::from_str("placeholder-present"))
+            .build();
+        let _registration = Registration::from_sync_root_id(&sync_root_id)
+            .register_scoped(&path)
+            .unwrap();
+
+        // deletes are not approved by the filter, so leftovers are removed before connecting
+        let file = path.join("present.txt");
+        let _ = fs::remove_file(&file);
+        let connection = Session::new()
+            .index(false)
+            .connect(&path, CountingFilter::default())
+            .unwrap();
+
+        let data = b"already present";
+        PlaceholderFile::new("present.txt")
+            .unwrap()
+            .metadata(Metadata::file().size(data.len() as u64))
+            .create_present(&path, &data[..])
+            .unwrap();
+
+        assert_eq!(fs::read(&file).unwrap(), data);
+        assert_eq!(connection.filter().fetches.load(Ordering::SeqCst), 0);
+
+        connection.disconnect().unwrap();
+        fs::remove_file(&file).unwrap();
+    }
+
+    #[test]
+    fn create_with_pin_state_sets_pin_state() {
+        let path = env::temp_dir().join("wincs-placeholder-pinned");
+        fs::create_dir_all(&path).unwrap();
+
+        let sync_root_id = SyncRootIdBuilder::new(U16String::from_str("wincs-test"))
+            .user_security_id(SecurityId::current_user().unwrap())
+            .account_name(U16String::from_str("placeholder-pinned"))
+            .build();
+        let _registration = Registration::from_sync_root_id(&sync_root_id)
+            .allow_pinning()
+            .register_scoped(&path)
+            .unwrap();
+
+        let file = path.join("unpinned.txt");
+        let _ = fs::remove_file(&file);
+        PlaceholderFile::new("unpinned.txt")
+            .unwrap()
+            .metadata(Metadata::file().size(4096))
+            .mark_sync()
+            .create_with_pin_state(&path, PinState::Unpinned)
+            .unwrap();
+
+        let info = walk::open_attributes(&file)
+            .unwrap()
+            .placeholder_info()
+            .unwrap();
+        assert!(matches!(info.pin_state(), PinState::Unpinned));
+        // opening the placeholder to set its pin state must not hydrate it
+        assert_eq!(info.on_disk_data_size(), 0);
+
+        fs::remove_file(&file).unwrap();
+    }
+}
@@ -0,0 +1,135 @@
+use std::mem;
+
+use windows::Win32::{
+    Foundation::NTSTATUS,
+    System::SystemInformation::{GetSystemInfo, OSVERSIONINFOW, SYSTEM_INFO},
+};
+
+// `RtlGetVersion` is used over `GetVersionExW` as the latter reports the version the application
+// is manifested for rather than the actual version
+#[link(name = "ntdll")]
+extern "system" {
+    fn RtlGetVersion(info: *mut OSVERSIONINFOW) -> NTSTATUS;
+}
+
+/// Capabilities of the Cloud Filter API that depend on the version of Windows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Feature {
+    /// The core Cloud Filter API, available since Windows 10 version 1709 (build 16299).
+    CloudFilter,
+    /// Custom states shown in the file explorer, available since Windows 10 version 1809 (build
+    /// 17763).
+    CustomStates,
+    /// [is_supported][crate::is_supported], available since Windows 10 version 2004 (build
+    /// 19041).
+    SupportQuery,
+    /// The sync status UI in the file explorer, available since Windows 11 version 22H2 (build
+    /// 22621).
+    StatusUi,
+}
+
+impl Feature {
+    /// The minimum build of Windows that supports the feature.
+    pub fn min_build(&self) -> u32 {
+        match self {
+            Self::CloudFilter => 16299,
+            Self::CustomStates => 17763,
+            Self::SupportQuery => 19041,
+            Self::StatusUi => 22621,
+        }
+    }
+}
+
+/// The minimum build of Windows required by this crate.
+pub fn min_build() -> u32 {
+    Feature::CloudFilter.min_build()
+}
+
+/// The build number of the running version of Windows.
+pub fn build() -> u32 {
+    let mut info = OSVERSIONINFOW {
+        dwOSVersionInfoSize: mem::size_of::<OSVERSIONINFOW>() as u32,
+        ..Default::default()
+    };
+
+    // this function is documented to always succeed
+    unsafe { RtlGetVersion(&mut info as *mut _) };
+    info.dwBuildNumber
+}
+
+/// Whether or not the running version of Windows supports the given feature.
+///
+/// This only checks the build number, it does not take into account whether or not the feature
+/// was disabled, e.g. by a group policy.
+pub fn supports_feature(feature: Feature) -> bool {
+    supported_on(build(), feature)
+}
+
+fn supported_on(build: u32, feature: Feature) -> bool {
+    build >= feature.min_build()
+}
+
+/// The alignment required of data transferred to a placeholder, which is the page size of the
+/// system.
+///
+/// [CfExecute](https://docs.microsoft.com/en-us/windows/win32/api/cfapi/nf-cfapi-cfexecute)
+/// rejects transfers whose offset or length is not a multiple of 4KiB, unless the transfer ends on
+/// the logical size of the file. The page size is never smaller than that, so buffers sized in
+/// multiples of the returned value are always accepted.
+pub fn transfer_alignment() -> usize {
+    let mut info = SYSTEM_INFO::default();
+    unsafe { GetSystemInfo(&mut info as *mut _) };
+    info.dwPageSize as usize
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FEATURES: [Feature; 4] = [
+        Feature::CloudFilter,
+        Feature::CustomStates,
+        Feature::SupportQuery,
+        Feature::StatusUi,
+    ];
+
+    #[test]
+    fn min_build_is_the_lowest_tier() {
+        assert_eq!(min_build(), Feature::CloudFilter.min_build());
+        assert!(FEATURES
+            .windows(2)
+            .all(|pair| pair[0].min_build() < pair[1].min_build()));
+    }
+
+    #[test]
+    fn supported_on_bounds() {
+        for feature in FEATURES {
+            assert!(!supported_on(feature.min_build() - 1, feature));
+            assert!(supported_on(feature.min_build(), feature));
+            assert!(supported_on(feature.min_build() + 1, feature));
+        }
+        // a build between two tiers supports only the lower one
+        assert!(supported_on(18362, Feature::CustomStates));
+        assert!(!supported_on(18362, Feature::SupportQuery));
+    }
+
+    #[test]
+    fn supports_feature_matches_build() {
+        let build = build();
+        assert!(build >= min_build());
+        for feature in FEATURES {
+            assert_eq!(supports_feature(feature), build >= feature.min_build());
+        }
+    }
+
+    #[test]
+    fn transfer_alignment_is_the_page_size() {
+        let mut info = SYSTEM_INFO::default();
+        unsafe { GetSystemInfo(&mut info as *mut _) };
+
+        let alignment = transfer_alignment();
+        assert_eq!(alignment, info.dwPageSize as usize);
+        assert!(alignment.is_power_of_two());
+        assert!(alignment >= 4096);
+    }
+}
@@ -0,0 +1,5 @@
+pub use crate::{
+    ext::{FileExt, PathExt},
+    filter::SyncFilter,
+    placeholder_file::BatchCreate,
+};
@@ -1,9 +1,19 @@
-use std::{path::PathBuf, slice};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    slice,
+};
 
 use widestring::{U16CStr, U16CString};
-use windows::Win32::Storage::CloudFilters::{CF_CALLBACK_INFO, CF_PROCESS_INFO};
+use windows::{
+    core,
+    Win32::Storage::CloudFilters::{CF_CALLBACK_INFO, CF_PROCESS_INFO},
+};
 
-use crate::placeholder::Placeholder;
+use crate::{
+    correlation_vector::CorrelationVector, filter::ticket, placeholder::Placeholder,
+    utility::io_to_core,
+};
 
 pub type RawConnectionKey = isize;
 pub type RawTransferKey = i64;
@@ -40,6 +50,21 @@ impl Request {
         unsafe { U16CStr::from_ptr_str(self.0.VolumeGuidName.0) }
     }
 
+    /// The raw callback information passed by the operating system.
+    ///
+    /// The pointers within the struct are only valid for the duration of the callback, so they
+    /// must not be read after the callback returns, even if the [Request][crate::Request] is kept
+    /// alive.
+    #[cfg(feature = "raw")]
+    pub fn raw(&self) -> &CF_CALLBACK_INFO {
+        &self.0
+    }
+
+    /// The GUID path of the current volume as a [PathBuf][std::path::PathBuf].
+    pub fn volume_guid_pathbuf(&self) -> PathBuf {
+        PathBuf::from(self.volume_guid_path().to_os_string())
+    }
+
     /// The letter of the current volume.
     ///
     /// The returned value comes in the form `X:`, where `X` is the drive letter.
@@ -75,6 +100,16 @@ impl Request {
         self.0.FileSize as u64
     }
 
+    /// Whether or not the placeholder is a directory.
+    ///
+    /// The callback information does not describe the type of the placeholder, so its attributes
+    /// are read from the file system. This does not hydrate the placeholder.
+    pub fn is_directory(&self) -> core::Result<bool> {
+        fs::metadata(self.path())
+            .map(|metadata| metadata.is_dir())
+            .map_err(io_to_core)
+    }
+
     // TODO: Create a U16Path struct to avoid an extra allocation
     // For now this should be cached on creation
     /// The absolute path of the placeholder file/directory starting from the root directory of the
@@ -85,11 +120,43 @@ impl Request {
     pub fn path(&self) -> PathBuf {
         let mut path =
             PathBuf::from(unsafe { U16CStr::from_ptr_str(self.0.VolumeDosName.0) }.to_os_string());
-        path.push(unsafe { U16CStr::from_ptr_str(self.0.NormalizedPath.0) }.to_os_string());
+        path.push(self.normalized_path().to_os_string());
 
         path
     }
 
+    /// The absolute path of the placeholder file/directory starting from the root directory of the
+    /// volume, without the volume itself.
+    ///
+    /// The returned value comes in the form `\path\to\placeholder`.
+    pub fn normalized_path(&self) -> &U16CStr {
+        unsafe { U16CStr::from_ptr_str(self.0.NormalizedPath.0) }
+    }
+
+    /// The path of the placeholder file/directory relative to the given root, usually the path of
+    /// the sync root.
+    ///
+    /// Returns `None` if the placeholder is not within the root.
+    pub fn relative_path(&self, root: impl AsRef<Path>) -> Option<PathBuf> {
+        self.path().strip_prefix(root).ok().map(Path::to_path_buf)
+    }
+
+    /// The absolute path of the placeholder file/directory starting from the GUID path of the
+    /// volume.
+    ///
+    /// Unlike [Request::path][crate::Request::path], the returned path does not rely on the volume
+    /// having a drive letter assigned. The returned value comes in the form
+    /// `\\?\Volume{GUID}\path\to\placeholder`.
+    pub fn guid_path(&self) -> PathBuf {
+        let mut path = self.volume_guid_path().to_ustring();
+        if path.as_slice().last() == Some(&(b'\\' as u16)) {
+            path.truncate(path.len() - 1);
+        }
+        path.push(self.normalized_path());
+
+        PathBuf::from(path.to_os_string())
+    }
+
     /// A numeric scale ranging from
     /// 0-[15](https://microsoft.github.io/windows-docs-rs/doc/windows/Win32/Storage/CloudFilters/constant.CF_MAX_PRIORITY_HINT.html)
     /// to describe the priority of the file operation.
@@ -116,6 +183,12 @@ impl Request {
         }
     }
 
+    /// The correlation vector of the file operation, if the operating system provided one.
+    pub fn correlation_vector(&self) -> Option<CorrelationVector> {
+        (!self.0.CorrelationVector.is_null())
+            .then(|| CorrelationVector(unsafe { *self.0.CorrelationVector }))
+    }
+
     /// The byte slice assigned to the current sync root on registration.
     pub fn register_blob(&self) -> &[u8] {
         unsafe {
@@ -137,6 +210,23 @@ impl Request {
         )
     }
 
+    /// Copies the essential information of the request into an owned
+    /// [RequestSnapshot][crate::RequestSnapshot].
+    ///
+    /// A [Request][crate::Request] borrows data owned by the operating system for the duration
+    /// of the callback, so it can not be moved to another thread. The snapshot could be used to
+    /// defer the operation to a thread pool and complete it later.
+    pub fn snapshot(&self) -> RequestSnapshot {
+        RequestSnapshot {
+            connection_key: self.connection_key(),
+            transfer_key: self.transfer_key(),
+            path: self.path(),
+            file_size: self.file_size(),
+            file_blob: self.file_blob().to_vec(),
+            register_blob: self.register_blob().to_vec(),
+        }
+    }
+
     // https://docs.microsoft.com/en-us/windows/win32/api/cfapi/ne-cfapi-cf_callback_type#remarks
     // after 60 seconds of no report, windows will cancel the request with an error,
     // this function is a "hack" to avoid the timeout
@@ -148,6 +238,69 @@ impl Request {
     pub fn reset_timeout() {}
 }
 
+/// An owned copy of the essential information of a [Request][crate::Request], created by
+/// [Request::snapshot][crate::Request::snapshot].
+///
+/// The operation must still be completed before it times out, read
+/// [Request::reset_timeout][crate::Request::reset_timeout] for more information.
+#[derive(Debug, Clone)]
+pub struct RequestSnapshot {
+    connection_key: RawConnectionKey,
+    transfer_key: RawTransferKey,
+    path: PathBuf,
+    file_size: u64,
+    file_blob: Vec<u8>,
+    register_blob: Vec<u8>,
+}
+
+impl RequestSnapshot {
+    /// A raw connection key used to identify the connection.
+    pub fn connection_key(&self) -> RawConnectionKey {
+        self.connection_key
+    }
+
+    /// A raw transfer key used to identify the current file operation.
+    pub fn transfer_key(&self) -> RawTransferKey {
+        self.transfer_key
+    }
+
+    /// The absolute path of the placeholder file/directory.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The logical size of the placeholder file.
+    pub fn file_size(&self) -> u64 {
+        self.file_size
+    }
+
+    /// The byte slice assigned to the placeholder file/directory.
+    pub fn file_blob(&self) -> &[u8] {
+        &self.file_blob
+    }
+
+    /// The byte slice assigned to the sync root on registration.
+    pub fn register_blob(&self) -> &[u8] {
+        &self.register_blob
+    }
+
+    /// Creates a new [Placeholder][crate::Placeholder] struct to perform various operations on the
+    /// placeholder file/directory.
+    pub fn placeholder(&self) -> Placeholder {
+        Placeholder::new(
+            self.connection_key,
+            self.transfer_key,
+            self.path.clone(),
+            self.file_size,
+        )
+    }
+
+    /// Rebuilds the [FetchData][crate::ticket::FetchData] ticket of the request.
+    pub fn ticket(&self) -> ticket::FetchData {
+        ticket::FetchData::new(self.connection_key, self.transfer_key)
+    }
+}
+
 /// Information about the calling process.
 #[derive(Debug)]
 pub struct Process(CF_PROCESS_INFO);
@@ -194,3 +347,43 @@ impl Process {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use windows::core::PCWSTR;
+
+    use super::*;
+
+    fn relative_path(normalized_path: &str, root: &str) -> Option<PathBuf> {
+        let volume = U16CString::from_str("C:").unwrap();
+        let normalized_path = U16CString::from_str(normalized_path).unwrap();
+        let request = Request::new(CF_CALLBACK_INFO {
+            VolumeDosName: PCWSTR(volume.as_ptr()),
+            NormalizedPath: PCWSTR(normalized_path.as_ptr()),
+            ..Default::default()
+        });
+        assert_eq!(request.normalized_path(), normalized_path.as_ucstr());
+
+        request.relative_path(root)
+    }
+
+    #[test]
+    fn relative_path_nested() {
+        assert_eq!(
+            relative_path("\\SyncRoot\\dir\\file.txt", "C:\\SyncRoot"),
+            Some(PathBuf::from("dir\\file.txt"))
+        );
+        assert_eq!(
+            relative_path("\\SyncRoot", "C:\\SyncRoot"),
+            Some(PathBuf::new())
+        );
+    }
+
+    #[test]
+    fn relative_path_outside_root() {
+        assert_eq!(relative_path("\\Other\\file.txt", "C:\\SyncRoot"), None);
+        // the root must match whole components, not just a prefix of the string
+        assert_eq!(relative_path("\\SyncRoot2\\file.txt", "C:\\SyncRoot"), None);
+        assert_eq!(relative_path("\\SyncRoot\\file.txt", "D:\\SyncRoot"), None);
+    }
+}
@@ -1,9 +1,31 @@
+use std::{
+    any::Any,
+    fs, mem,
+    path::{Component, Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
+
 use windows::{
     core,
-    Win32::Storage::CloudFilters::{CfDisconnectSyncRoot, CF_CONNECTION_KEY},
+    Win32::{
+        Foundation,
+        Storage::CloudFilters::{CfDisconnectSyncRoot, CF_CONNECTION_KEY},
+    },
 };
 
-use crate::{filter::Callbacks, request::RawConnectionKey};
+use crate::{
+    error::CloudErrorKind,
+    ext::{FileExt, PlaceholderInfo, ProviderStatus, UpdateOptions},
+    filter::{Callbacks, Inflight},
+    placeholder::Placeholder,
+    request::RawConnectionKey,
+    root::watcher::{self, Watcher},
+    utility::io_to_core,
+    walk::{self, Walk},
+};
 
 /// A handle to the current session for a given sync root.
 ///
@@ -15,42 +37,285 @@ use crate::{filter::Callbacks, request::RawConnectionKey};
 /// [Connection::disconnect][crate::Connection::disconnect] is called implicitly when the struct is
 /// dropped. To handle possible errors, be sure to call
 /// [Connection::disconnect][crate::Connection::disconnect] explicitly.
+///
+/// When dropped, the sync root is disconnected first, which guarantees no more callbacks are
+/// invoked, then the thread notifying
+/// [SyncFilter::status_changed][crate::SyncFilter::status_changed] is stopped, and only then is
+/// the [SyncFilter][crate::SyncFilter] dropped.
 #[derive(Debug)]
 pub struct Connection<T> {
-    connection_key: RawConnectionKey,
+    // fields are dropped in order of declaration, read the docs above before reordering
+    _watcher: Watcher,
+    // the callback context, which must outlive every callback
+    context: Option<Box<dyn Any + Send + Sync>>,
+    handle: ConnectionHandle<T>,
+    inflight: Arc<Inflight>,
+    disconnected: AtomicBool,
     _callbacks: Callbacks,
-    filter: T,
 }
 
 // this struct could house many more windows api functions, although they all seem to do nothing
 // according to the threads on microsoft q&a
 impl<T> Connection<T> {
-    pub(crate) fn new(connection_key: RawConnectionKey, callbacks: Callbacks, filter: T) -> Self {
+    pub(crate) fn new(
+        connection_key: RawConnectionKey,
+        path: PathBuf,
+        callbacks: Callbacks,
+        watcher: Watcher,
+        inflight: Arc<Inflight>,
+        context: Box<dyn Any + Send + Sync>,
+        filter: T,
+    ) -> Self {
         Self {
-            connection_key,
+            _watcher: watcher,
+            context: Some(context),
+            handle: ConnectionHandle {
+                connection_key,
+                path: path.into(),
+                filter,
+            },
+            inflight,
+            disconnected: AtomicBool::new(false),
             _callbacks: callbacks,
-            filter,
         }
     }
 
     /// A raw connection key used to identify the connection.
     pub fn connection_key(&self) -> RawConnectionKey {
-        self.connection_key
+        self.handle.connection_key
+    }
+
+    /// The path to the connected sync root.
+    pub fn path(&self) -> &Path {
+        &self.handle.path
+    }
+
+    /// The current status of the sync provider.
+    pub fn status(&self) -> core::Result<ProviderStatus> {
+        self.handle.status()
+    }
+
+    /// Lazily walks every placeholder beneath the sync root, yielding its path and
+    /// [PlaceholderInfo][crate::ext::PlaceholderInfo]. Entries that are not placeholders are
+    /// skipped.
+    ///
+    /// Every entry is opened to read its state, although without read access, so no placeholder
+    /// is hydrated. However, reading a placeholder directory that has not been populated will
+    /// invoke [SyncFilter::fetch_placeholders][crate::SyncFilter::fetch_placeholders]. For large
+    /// sync roots, this operation could be costly.
+    pub fn walk_placeholders(
+        &self,
+    ) -> impl Iterator<Item = core::Result<(PathBuf, PlaceholderInfo)>> {
+        self.handle.walk_placeholders()
+    }
+
+    /// Rewrites the blob of every placeholder beneath the sync root, useful when migrating to a
+    /// new blob format without hydrating any placeholder.
+    ///
+    /// The mapper is called with the path and current blob of each placeholder. If it returns
+    /// `Some`, the blob is replaced, otherwise the placeholder is left untouched. The in-sync state
+    /// of each placeholder is preserved. The result of each update is returned alongside its
+    /// path, while an error walking the sync root stops the migration.
+    pub fn remap_blobs(
+        &self,
+        mapper: impl Fn(&Path, &[u8]) -> Option<Vec<u8>>,
+    ) -> core::Result<Vec<(PathBuf, core::Result<()>)>> {
+        self.handle.remap_blobs(mapper)
+    }
+
+    /// The total number of bytes of the placeholders beneath the sync root that are present on
+    /// the disk, i.e. hydrated.
+    ///
+    /// This walks the entire sync root, read
+    /// [Connection::walk_placeholders][crate::Connection::walk_placeholders] for the cost of the
+    /// walk. Files that are not placeholders are not counted.
+    pub fn on_disk_usage(&self) -> core::Result<u64> {
+        self.handle.on_disk_usage()
+    }
+
+    /// The total logical size of the placeholder files beneath the sync root, regardless of how
+    /// much of them is present on the disk.
+    ///
+    /// This walks the entire sync root, read
+    /// [Connection::walk_placeholders][crate::Connection::walk_placeholders] for the cost of the
+    /// walk. Files that are not placeholders are not counted.
+    pub fn logical_usage(&self) -> core::Result<u64> {
+        self.handle.logical_usage()
+    }
+
+    /// Creates a [Placeholder][crate::Placeholder] to perform operations on the placeholder at the
+    /// given path outside of a callback.
+    ///
+    /// A relative path is resolved against the sync root. An error is returned if the path is not
+    /// within the sync root, read [Placeholder::from_path][crate::Placeholder::from_path] for more
+    /// information.
+    pub fn placeholder(&self, path: impl AsRef<Path>) -> core::Result<Placeholder> {
+        self.handle.placeholder(path)
+    }
+
+    /// The amount of operations handed to the [SyncFilter][crate::SyncFilter] that have not yet
+    /// completed.
+    ///
+    /// An operation is considered complete once its ticket is dropped.
+    pub fn inflight_count(&self) -> usize {
+        self.inflight.len()
     }
 
     /// A reference to the inner [SyncFilter][crate::SyncFilter] struct.
     pub fn filter(&self) -> &T {
-        &self.filter
+        &self.handle.filter
+    }
+
+    /// Creates a [ConnectionHandle][crate::ConnectionHandle] that could be shared across threads
+    /// and subsystems.
+    pub fn handle(&self) -> ConnectionHandle<T>
+    where
+        T: Clone,
+    {
+        self.handle.clone()
     }
 
     /// Disconnects the sync root, read [Connection][crate::Connection] for more information.
+    ///
+    /// Operations that have not yet completed, read
+    /// [Connection::inflight_count][crate::Connection::inflight_count], are failed with
+    /// [CloudErrorKind::RequestAborted][crate::CloudErrorKind::RequestAborted] beforehand, so
+    /// they are not left dangling.
     pub fn disconnect(self) -> core::Result<()> {
         self.disconnect_ref()
     }
 
     #[inline]
     fn disconnect_ref(&self) -> core::Result<()> {
-        unsafe { CfDisconnectSyncRoot(CF_CONNECTION_KEY(self.connection_key)) }
+        if self.disconnected.load(Ordering::Acquire) {
+            return Ok(());
+        }
+
+        self.inflight
+            .abort_all(self.handle.connection_key, CloudErrorKind::RequestAborted);
+        unsafe { CfDisconnectSyncRoot(CF_CONNECTION_KEY(self.handle.connection_key)) }?;
+        self.disconnected.store(true, Ordering::Release);
+
+        Ok(())
+    }
+}
+
+/// A cloneable handle to a [Connection][crate::Connection], created by
+/// [Connection::handle][crate::Connection::handle].
+///
+/// A handle could perform operations within the sync root, although it does not own the
+/// connection. Only the original [Connection][crate::Connection] disconnects the sync root, after
+/// which operations performed through the handle will fail.
+#[derive(Debug, Clone)]
+pub struct ConnectionHandle<T> {
+    connection_key: RawConnectionKey,
+    path: Arc<Path>,
+    filter: T,
+}
+
+impl<T> ConnectionHandle<T> {
+    /// A raw connection key used to identify the connection.
+    pub fn connection_key(&self) -> RawConnectionKey {
+        self.connection_key
+    }
+
+    /// The path to the connected sync root.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The current status of the sync provider.
+    pub fn status(&self) -> core::Result<ProviderStatus> {
+        watcher::provider_status(&self.path)
+    }
+
+    /// Lazily walks every placeholder beneath the sync root, read
+    /// [Connection::walk_placeholders][crate::Connection::walk_placeholders] for more information.
+    pub fn walk_placeholders(
+        &self,
+    ) -> impl Iterator<Item = core::Result<(PathBuf, PlaceholderInfo)>> {
+        Walk::new(self.path.as_ref()).filter_map(|entry| {
+            let path = match entry {
+                Ok(entry) => entry.path(),
+                Err(err) => return Some(Err(io_to_core(err))),
+            };
+
+            let file = match walk::open_attributes(&path) {
+                Ok(file) => file,
+                Err(err) => return Some(Err(io_to_core(err))),
+            };
+
+            match file.is_placeholder() {
+                Ok(true) => Some(file.placeholder_info().map(|info| (path, info))),
+                Ok(false) => None,
+                Err(err) => Some(Err(err)),
+            }
+        })
+    }
+
+    /// Creates a [Placeholder][crate::Placeholder] for the placeholder at the given path, read
+    /// [Connection::placeholder][crate::Connection::placeholder] for more information.
+    pub fn placeholder(&self, path: impl AsRef<Path>) -> core::Result<Placeholder> {
+        let path = self.path.join(path);
+        // parent components could lead outside of the sync root while still sharing its prefix
+        if !path.starts_with(&self.path)
+            || path
+                .components()
+                .any(|component| component == Component::ParentDir)
+        {
+            return Err(core::Error::new(
+                Foundation::E_INVALIDARG,
+                format!("{path:?} is not within the sync root").into(),
+            ));
+        }
+
+        Placeholder::from_path(self.connection_key, path)
+    }
+
+    /// Rewrites the blob of every placeholder beneath the sync root, read
+    /// [Connection::remap_blobs][crate::Connection::remap_blobs] for more information.
+    pub fn remap_blobs(
+        &self,
+        mapper: impl Fn(&Path, &[u8]) -> Option<Vec<u8>>,
+    ) -> core::Result<Vec<(PathBuf, core::Result<()>)>> {
+        let mut results = Vec::new();
+        for entry in self.walk_placeholders() {
+            let (path, info) = entry?;
+            if let Some(blob) = mapper(&path, info.blob()) {
+                let result = remap_blob(&path, &blob, info.is_synced());
+                results.push((path, result));
+            }
+        }
+
+        Ok(results)
+    }
+
+    /// The total number of bytes of the placeholders beneath the sync root that are present on
+    /// the disk, read [Connection::on_disk_usage][crate::Connection::on_disk_usage] for more
+    /// information.
+    pub fn on_disk_usage(&self) -> core::Result<u64> {
+        self.walk_placeholders()
+            .map(|entry| entry.map(|(_, info)| info.on_disk_data_size()))
+            .sum()
+    }
+
+    /// The total logical size of the placeholder files beneath the sync root, read
+    /// [Connection::logical_usage][crate::Connection::logical_usage] for more information.
+    pub fn logical_usage(&self) -> core::Result<u64> {
+        self.walk_placeholders()
+            .map(|entry| {
+                let (path, _) = entry?;
+                // directories have no logical size of their own
+                fs::metadata(path)
+                    .map(|metadata| if metadata.is_dir() { 0 } else { metadata.len() })
+                    .map_err(io_to_core)
+            })
+            .sum()
+    }
+
+    /// A reference to the inner [SyncFilter][crate::SyncFilter] struct.
+    pub fn filter(&self) -> &T {
+        &self.filter
     }
 }
 
@@ -60,5 +325,169 @@ impl<T> Drop for Connection<T> {
         {
             self.disconnect_ref();
         }
+
+        // no more callbacks are invoked once the sync root is disconnected, so the context could
+        // be freed, otherwise it's leaked as a late callback could still read it
+        if !*self.disconnected.get_mut() {
+            mem::forget(self.context.take());
+        }
+    }
+}
+
+fn remap_blob(path: &Path, blob: &[u8], synced: bool) -> core::Result<()> {
+    let mut options = UpdateOptions::default().blob(blob);
+    // the in-sync state is explicitly kept so that changing the blob isn't considered a change
+    if synced {
+        options = options.mark_sync();
+    }
+
+    // a usn of 0 skips the usn check
+    walk::open_write(path)
+        .map_err(io_to_core)?
+        .update(0, options)
+        .map(|_| ())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, sync::Weak, thread};
+
+    use widestring::U16String;
+
+    use super::*;
+    use crate::{
+        Metadata, PlaceholderFile, Registration, SecurityId, Session, SyncFilter, SyncRootIdBuilder,
+    };
+
+    struct EmptyFilter;
+
+    impl SyncFilter for EmptyFilter {}
+
+    #[test]
+    fn filter_is_dropped_after_disconnect() {
+        let path = env::temp_dir().join("wincs-connection-drop");
+        fs::create_dir_all(&path).unwrap();
+
+        let sync_root_id = SyncRootIdBuilder::new(U16String::from_str("wincs-test"))
+            .user_security_id(SecurityId::current_user().unwrap())
+            .account_name(U16String::from_str("connection-drop"))
+            .build();
+        let _registration = Registration::from_sync_root_id(&sync_root_id)
+            .register_scoped(&path)
+            .unwrap();
+
+        // repeatedly connecting must not leak a filter (and its callback context) per connection
+        for i in 0..64 {
+            let session = Session::new().index(false);
+            let connection = session.connect(&path, EmptyFilter).unwrap();
+            let filter: Weak<EmptyFilter> = Arc::downgrade(connection.filter());
+
+            if i % 2 == 0 {
+                connection.disconnect().unwrap();
+            } else {
+                drop(connection);
+            }
+            assert!(filter.upgrade().is_none());
+        }
+    }
+
+    #[test]
+    fn handle_is_usable_from_another_thread() {
+        let path = env::temp_dir().join("wincs-connection-handle");
+        fs::create_dir_all(&path).unwrap();
+
+        let sync_root_id = SyncRootIdBuilder::new(U16String::from_str("wincs-test"))
+            .user_security_id(SecurityId::current_user().unwrap())
+            .account_name(U16String::from_str("connection-handle"))
+            .build();
+        let _registration = Registration::from_sync_root_id(&sync_root_id)
+            .register_scoped(&path)
+            .unwrap();
+
+        let file = path.join("handle.txt");
+        let _ = fs::remove_file(&file);
+        PlaceholderFile::new("handle.txt")
+            .unwrap()
+            .metadata(Metadata::file())
+            .create(&path)
+            .unwrap();
+
+        let connection = Session::new()
+            .index(false)
+            .connect(&path, EmptyFilter)
+            .unwrap();
+        let handle = connection.handle();
+
+        let cloned = handle.clone();
+        thread::spawn(move || {
+            cloned.status().unwrap();
+            cloned
+                .placeholder("handle.txt")
+                .unwrap()
+                .mark_sync()
+                .unwrap();
+            assert_eq!(cloned.walk_placeholders().count(), 1);
+        })
+        .join()
+        .unwrap();
+        assert!(walk::open_attributes(&file)
+            .unwrap()
+            .placeholder_info()
+            .unwrap()
+            .is_synced());
+
+        connection.disconnect().unwrap();
+        // the handle outlives the connection, although it no longer owns it
+        assert_eq!(handle.path(), path);
+        assert_eq!(Arc::strong_count(handle.filter()), 1);
+
+        fs::remove_file(&file).unwrap();
+    }
+
+    #[test]
+    fn on_disk_usage_excludes_dehydrated_data() {
+        let path = env::temp_dir().join("wincs-connection-usage");
+        fs::create_dir_all(&path).unwrap();
+
+        let sync_root_id = SyncRootIdBuilder::new(U16String::from_str("wincs-test"))
+            .user_security_id(SecurityId::current_user().unwrap())
+            .account_name(U16String::from_str("connection-usage"))
+            .build();
+        let _registration = Registration::from_sync_root_id(&sync_root_id)
+            .register_scoped(&path)
+            .unwrap();
+
+        let data = [1; 4096];
+
+        // deletes are not approved by the filter, so leftovers are removed before connecting
+        let hydrated = path.join("hydrated.bin");
+        let dehydrated = path.join("dehydrated.bin");
+        let _ = fs::remove_file(&hydrated);
+        let _ = fs::remove_file(&dehydrated);
+        PlaceholderFile::new("hydrated.bin")
+            .unwrap()
+            .metadata(Metadata::file().size(data.len() as u64))
+            .create_present(&path, &data[..])
+            .unwrap();
+        PlaceholderFile::new("dehydrated.bin")
+            .unwrap()
+            .metadata(Metadata::file().size(data.len() as u64))
+            .mark_sync()
+            .create(&path)
+            .unwrap();
+
+        let connection = Session::new()
+            .index(false)
+            .connect(&path, EmptyFilter)
+            .unwrap();
+
+        let on_disk = connection.on_disk_usage().unwrap();
+        let logical = connection.logical_usage().unwrap();
+        assert_eq!(logical, 2 * data.len() as u64);
+        assert!(on_disk < logical);
+
+        connection.disconnect().unwrap();
+        fs::remove_file(&hydrated).unwrap();
+        fs::remove_file(&dehydrated).unwrap();
     }
 }
@@ -2,11 +2,15 @@ mod connect;
 mod register;
 mod session;
 mod sync_root;
+mod watcher;
 
-pub use connect::Connection;
+pub use connect::{Connection, ConnectionHandle};
 pub use register::{
-    HydrationPolicy, HydrationType, PopulationType, ProtectionMode, Registration,
-    SupportedAttributes,
+    HydrationPolicy, HydrationType, PopulationType, ProtectionMode, Registration, ShellHandlers,
+    SupportedAttributes, SyncRootRegistration,
+};
+pub use session::{deindex_path, Session};
+pub use sync_root::{
+    active_roots, is_supported, unregister_provider, InvalidName, SecurityId, SyncRootId,
+    SyncRootIdBuilder,
 };
-pub use session::Session;
-pub use sync_root::{active_roots, is_supported, SecurityId, SyncRootId, SyncRootIdBuilder};
@@ -1,9 +1,10 @@
-use std::path::Path;
+use std::{env, mem, path::Path};
 
-use widestring::{U16Str, U16String};
+use widestring::{U16CString, U16Str, U16String};
 use windows::{
-    core::{self, GUID},
+    core::{self, GUID, HRESULT, PCWSTR},
     Foundation::Uri,
+    Security::Cryptography::CryptographicBuffer,
     Storage::{
         Provider::{
             StorageProviderHardlinkPolicy, StorageProviderHydrationPolicy,
@@ -12,16 +13,23 @@ use windows::{
             StorageProviderSyncRootInfo, StorageProviderSyncRootManager,
         },
         StorageFolder,
-        Streams::DataWriter,
     },
-    Win32::Storage::CloudFilters::{
-        self, CF_HYDRATION_POLICY_MODIFIER_USHORT, CF_HYDRATION_POLICY_PRIMARY,
-        CF_HYDRATION_POLICY_PRIMARY_USHORT, CF_INSYNC_POLICY, CF_POPULATION_POLICY_PRIMARY,
-        CF_POPULATION_POLICY_PRIMARY_USHORT,
+    Win32::{
+        Foundation,
+        Storage::CloudFilters::{
+            self, CF_HYDRATION_POLICY_MODIFIER_USHORT, CF_HYDRATION_POLICY_PRIMARY,
+            CF_HYDRATION_POLICY_PRIMARY_USHORT, CF_INSYNC_POLICY, CF_POPULATION_POLICY_PRIMARY,
+            CF_POPULATION_POLICY_PRIMARY_USHORT,
+        },
+        System::Registry::{self, RegDeleteKeyValueW, RegSetKeyValueW},
     },
 };
 
-use crate::{utility::ToHString, SyncRootId};
+use crate::{
+    ext::PathExt,
+    utility::{io_to_core, ToHString},
+    SyncRootId,
+};
 
 #[derive(Debug, Clone)]
 pub struct Registration<'a> {
@@ -30,7 +38,7 @@ pub struct Registration<'a> {
     allow_pinning: bool,
     allow_hardlinks: bool,
     display_name: &'a U16Str,
-    recycle_bin_uri: Option<&'a U16Str>,
+    recycle_bin_uri: Option<Uri>,
     version: Option<&'a U16Str>,
     hydration_type: HydrationType,
     hydration_policy: HydrationPolicy,
@@ -40,6 +48,8 @@ pub struct Registration<'a> {
     supported_attributes: SupportedAttributes,
     icon: U16String,
     blob: Option<&'a [u8]>,
+    check_foreign_root: bool,
+    shell_handlers: Option<ShellHandlers>,
 }
 
 impl<'a> Registration<'a> {
@@ -60,6 +70,8 @@ impl<'a> Registration<'a> {
             supported_attributes: SupportedAttributes::default(),
             icon: U16String::from_str("C:\\Windows\\System32\\imageres.dll,1525"),
             blob: None,
+            check_foreign_root: true,
+            shell_handlers: None,
         }
     }
 
@@ -85,13 +97,21 @@ impl<'a> Registration<'a> {
         self
     }
 
-    pub fn recycle_bin_uri(mut self, uri: &'a U16Str) -> Self {
-        self.recycle_bin_uri = Some(uri);
-        self
+    /// The URI of the recycle bin of the sync provider, e.g. a web page listing the deleted files.
+    ///
+    /// The URI is parsed immediately, so an invalid URI is reported here rather than when
+    /// registering.
+    pub fn recycle_bin_uri(mut self, uri: &U16Str) -> core::Result<Self> {
+        self.recycle_bin_uri = Some(Uri::CreateUri(uri.to_hstring())?);
+        Ok(self)
     }
 
     // I think this is for sync roots with the same provider name?
-
+    /// Shows sibling sync roots of the same provider as a group in the file explorer.
+    ///
+    /// Like every other option, changing this on an existing sync root requires calling
+    /// [Registration::register][crate::Registration::register] again, the value could then be read
+    /// back through [SyncRootId::info][crate::SyncRootId::info].
     pub fn show_siblings_as_group(mut self) -> Self {
         self.show_siblings_as_group = true;
         self
@@ -137,6 +157,15 @@ impl<'a> Registration<'a> {
         self
     }
 
+    /// Uses the icon resource at the given index embedded within the current executable.
+    ///
+    /// The path of the executable is resolved via
+    /// [current_exe][std::env::current_exe], which could fail if the executable has been removed.
+    pub fn icon_from_exe(self, index: u16) -> core::Result<Self> {
+        let path = env::current_exe().map_err(io_to_core)?;
+        Ok(self.icon(U16String::from_os_str(path.as_os_str()), index))
+    }
+
     pub fn blob(mut self, blob: &'a [u8]) -> Self {
         assert!(
             blob.len() <= 65536,
@@ -147,11 +176,47 @@ impl<'a> Registration<'a> {
         self
     }
 
+    /// Associates shell extensions with the sync root, which are registered alongside it.
+    ///
+    /// Each handler is the CLSID of a COM server that must already be registered, e.g. under
+    /// `HKEY_CLASSES_ROOT\CLSID`, by the provider's installer. The handlers are written to the
+    /// registry key of the sync root, which requires administrator privileges, and are removed by
+    /// [SyncRootId::unregister][crate::SyncRootId::unregister].
+    pub fn with_shell_handlers(mut self, handlers: ShellHandlers) -> Self {
+        self.shell_handlers = Some(handlers);
+        self
+    }
+
+    /// Skips checking whether the path is already a sync root registered under a different
+    /// [SyncRootId][crate::SyncRootId] before registering, read [Registration::register][crate::Registration::register] for more
+    /// information.
+    pub fn skip_foreign_root_check(mut self) -> Self {
+        self.check_foreign_root = false;
+        self
+    }
+
+    /// Registers the sync root at the given path.
+    ///
+    /// If the path is already a sync root registered under a different
+    /// [SyncRootId][crate::SyncRootId], an error naming that id is returned, which maps to
+    /// [CloudErrorKind::AlreadyConnected][crate::CloudErrorKind::AlreadyConnected]. This check
+    /// could be disabled through
+    /// [Registration::skip_foreign_root_check][crate::Registration::skip_foreign_root_check].
+    ///
+    /// Registering a [SyncRootId][crate::SyncRootId] that is already registered will update the
+    /// existing registration in place. Every option is reapplied, so options that are not
+    /// specified revert to their defaults.
     pub fn register<P: AsRef<Path>>(&self, path: P) -> core::Result<()> {
+        self.hydration_policy.check()?;
+        if self.check_foreign_root {
+            check_foreign_root(path.as_ref(), self.sync_root_id)?;
+        }
+
         let info = StorageProviderSyncRootInfo::new()?;
 
         info.SetProtectionMode(self.protection_mode.into())?;
         info.SetShowSiblingsAsGroup(self.show_siblings_as_group)?;
+        info.SetAllowPinning(self.allow_pinning)?;
         info.SetHydrationPolicy(self.hydration_type.into())?;
         info.SetHydrationPolicyModifier(self.hydration_policy.0)?;
         info.SetPopulationPolicy(self.population_type.into())?;
@@ -179,16 +244,161 @@ impl<'a> Registration<'a> {
         }
 
         if let Some(uri) = &self.recycle_bin_uri {
-            info.SetRecycleBinUri(Uri::CreateUri(uri.to_hstring())?)?;
+            info.SetRecycleBinUri(uri)?;
         }
         if let Some(blob) = &self.blob {
-            // TODO: implement IBuffer interface for slices to avoid a copy
-            let writer = DataWriter::new()?;
-            writer.WriteBytes(blob)?;
-            info.SetContext(writer.DetachBuffer()?)?;
+            // copies the slice directly into the buffer without an intermediate writer
+            info.SetContext(CryptographicBuffer::CreateFromByteArray(blob)?)?;
+        }
+
+        StorageProviderSyncRootManager::Register(info)?;
+
+        if let Some(handlers) = &self.shell_handlers {
+            set_shell_handlers(self.sync_root_id, handlers)?;
         }
 
-        StorageProviderSyncRootManager::Register(info)
+        Ok(())
+    }
+
+    /// Registers the sync root at the given path and returns a guard that unregisters it when
+    /// dropped.
+    ///
+    /// This is useful for tests and ephemeral providers where the registration should not outlive
+    /// the process. Call [SyncRootRegistration::leak][crate::SyncRootRegistration::leak] to keep
+    /// the sync root registered.
+    pub fn register_scoped<P: AsRef<Path>>(&self, path: P) -> core::Result<SyncRootRegistration> {
+        self.register(path)?;
+        Ok(SyncRootRegistration(Some(self.sync_root_id.clone())))
+    }
+}
+
+/// The CLSIDs of the shell extensions associated with a sync root, read
+/// [Registration::with_shell_handlers][crate::Registration::with_shell_handlers].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ShellHandlers {
+    /// Provides thumbnails for placeholders that are not hydrated.
+    pub thumbnail: Option<GUID>,
+    /// Provides additional properties displayed for placeholders.
+    pub properties: Option<GUID>,
+    /// Provides the custom states displayed for placeholders.
+    pub custom_state: Option<GUID>,
+}
+
+impl ShellHandlers {
+    // the registry value names of each handler
+    const THUMBNAIL: &'static str = "ThumbnailProvider";
+    const PROPERTIES: &'static str = "ExtendedPropertyHandler";
+    const CUSTOM_STATE: &'static str = "CustomStateHandler";
+
+    fn entries(&self) -> [(&'static str, Option<GUID>); 3] {
+        [
+            (Self::THUMBNAIL, self.thumbnail),
+            (Self::PROPERTIES, self.properties),
+            (Self::CUSTOM_STATE, self.custom_state),
+        ]
+    }
+}
+
+// the key the operating system stores the registration of each sync root under
+fn sync_root_key(sync_root_id: &SyncRootId) -> U16CString {
+    let mut key = U16String::from_str(
+        "SOFTWARE\\Microsoft\\Windows\\CurrentVersion\\Explorer\\SyncRootManager\\",
+    );
+    key.push(sync_root_id.as_u16str());
+    U16CString::from_ustr_truncate(key)
+}
+
+fn set_shell_handlers(sync_root_id: &SyncRootId, handlers: &ShellHandlers) -> core::Result<()> {
+    let key = sync_root_key(sync_root_id);
+    for (name, clsid) in handlers.entries() {
+        if let Some(clsid) = clsid {
+            let value = U16CString::from_str_truncate(format!("{{{clsid:?}}}"));
+            unsafe {
+                HRESULT::from(RegSetKeyValueW(
+                    Registry::HKEY_LOCAL_MACHINE,
+                    PCWSTR(key.as_ptr()),
+                    name,
+                    Registry::REG_SZ.0,
+                    value.as_ptr() as *const _,
+                    mem::size_of_val(value.as_slice_with_nul()) as u32,
+                ))
+                .ok()?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Removes the shell handlers of the sync root, ignoring the handlers that were never set.
+pub(crate) fn remove_shell_handlers(sync_root_id: &SyncRootId) {
+    let key = sync_root_key(sync_root_id);
+    for (name, _) in ShellHandlers::default().entries() {
+        // the value doesn't exist if the handler was never set
+        unsafe {
+            RegDeleteKeyValueW(Registry::HKEY_LOCAL_MACHINE, PCWSTR(key.as_ptr()), name);
+        }
+    }
+}
+
+// returns an error if the path is already a sync root registered under a different id
+fn check_foreign_root(path: &Path, sync_root_id: &SyncRootId) -> core::Result<()> {
+    // the path is not a sync root, or it doesn't exist, which is left to the registration
+    let existing = match path.sync_root_info().and_then(|info| info.Id()) {
+        Ok(id) => id,
+        Err(_) => return Ok(()),
+    };
+
+    if &existing != sync_root_id.as_hstring() {
+        return Err(core::Error::new(
+            HRESULT::from(Foundation::ERROR_CLOUD_FILE_ALREADY_CONNECTED),
+            format!("the path is already the sync root {existing:?}").into(),
+        ));
+    }
+
+    Ok(())
+}
+
+/// A guard for a registered sync root created by
+/// [Registration::register_scoped][crate::Registration::register_scoped].
+///
+/// [SyncRootRegistration::unregister][crate::SyncRootRegistration::unregister] is called implicitly
+/// when the struct is dropped. To handle possible errors, be sure to call
+/// [SyncRootRegistration::unregister][crate::SyncRootRegistration::unregister] explicitly.
+#[derive(Debug)]
+pub struct SyncRootRegistration(Option<SyncRootId>);
+
+impl SyncRootRegistration {
+    /// The [SyncRootId][crate::SyncRootId] of the registered sync root.
+    pub fn sync_root_id(&self) -> &SyncRootId {
+        // the id is only taken when the guard is consumed
+        self.0.as_ref().unwrap()
+    }
+
+    /// Unregisters the sync root, read [SyncRootRegistration][crate::SyncRootRegistration] for
+    /// more information.
+    pub fn unregister(mut self) -> core::Result<()> {
+        self.0.take().unwrap().unregister()
+    }
+
+    /// Consumes the guard without unregistering the sync root, returning its
+    /// [SyncRootId][crate::SyncRootId].
+    ///
+    /// The sync root will stay registered until
+    /// [SyncRootId::unregister][crate::SyncRootId::unregister] is called.
+    pub fn leak(mut self) -> SyncRootId {
+        self.0.take().unwrap()
+    }
+}
+
+impl Drop for SyncRootRegistration {
+    fn drop(&mut self) {
+        if let Some(sync_root_id) = self.0.take() {
+            #[allow(unused_must_use)]
+            {
+                sync_root_id.unregister();
+            }
+        }
     }
 }
 
@@ -207,11 +417,22 @@ impl From<ProtectionMode> for StorageProviderProtectionMode {
     }
 }
 
+/// Determines how much of a placeholder file is hydrated when its content is read.
 #[derive(Debug, Clone, Copy)]
 pub enum HydrationType {
+    /// Only the ranges that are read are hydrated, the rest of the file is never requested.
     Partial,
+    /// Reads complete as soon as the range they require is transferred, while the operating system
+    /// keeps requesting the rest of the file in the background.
+    ///
+    /// Each call to [SyncFilter::fetch_data][crate::SyncFilter::fetch_data] only has to transfer
+    /// its required range before returning, later ranges arrive through subsequent callbacks. Read
+    /// [FetchData::transfer_required_from][crate::ticket::FetchData::transfer_required_from].
     Progressive,
+    /// The whole file is hydrated before a read completes.
     Full,
+    /// Same as [HydrationType::Full][crate::HydrationType::Full], except the file can never be
+    /// dehydrated.
     AlwaysFull,
 }
 
@@ -246,14 +467,22 @@ impl HydrationPolicy {
         Self::default()
     }
 
+    /// Requires the data of a placeholder to be validated through
+    /// [SyncFilter::validate_data][crate::SyncFilter::validate_data] after it is hydrated.
+    ///
+    /// This modifier is incompatible with
+    /// [HydrationPolicy::allow_streaming][crate::HydrationPolicy::allow_streaming].
     pub fn require_validation(mut self) -> Self {
         self.0 |= StorageProviderHydrationPolicyModifier::ValidationRequired;
         self
     }
 
-    // TODO: assert this, it is incompatible with the validation required parameter
     // https://docs.microsoft.com/en-us/windows/win32/api/cfapi/ne-cfapi-cf_hydration_policy_modifier
-
+    /// Allows the data of a placeholder to be streamed rather than stored on the disk.
+    ///
+    /// This modifier is incompatible with
+    /// [HydrationPolicy::require_validation][crate::HydrationPolicy::require_validation], as
+    /// streamed data is never persisted to be validated.
     pub fn allow_streaming(mut self) -> Self {
         self.0 |= StorageProviderHydrationPolicyModifier::StreamingAllowed;
         self
@@ -268,6 +497,25 @@ impl HydrationPolicy {
         self.0 |= StorageProviderHydrationPolicyModifier::AllowFullRestartHydration;
         self
     }
+
+    fn contains(&self, modifier: StorageProviderHydrationPolicyModifier) -> bool {
+        self.0 .0 & modifier.0 != 0
+    }
+
+    /// Returns a descriptive error if the policy contains incompatible modifiers.
+    pub(crate) fn check(&self) -> core::Result<()> {
+        if self.contains(StorageProviderHydrationPolicyModifier::ValidationRequired)
+            && self.contains(StorageProviderHydrationPolicyModifier::StreamingAllowed)
+        {
+            return Err(core::Error::new(
+                Foundation::E_INVALIDARG,
+                "the `require_validation` and `allow_streaming` hydration policies are incompatible"
+                    .into(),
+            ));
+        }
+
+        Ok(())
+    }
 }
 
 impl Default for HydrationPolicy {
@@ -380,3 +628,127 @@ impl From<CF_INSYNC_POLICY> for SupportedAttributes {
         Self(StorageProviderInSyncPolicy(policy.0))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::{CloudErrorKind, SecurityId, SyncRootIdBuilder};
+
+    #[test]
+    fn hydration_policy_check_default() {
+        assert!(HydrationPolicy::default().check().is_ok());
+    }
+
+    #[test]
+    fn hydration_policy_check_compatible_modifiers() {
+        let policies = [
+            HydrationPolicy::default().require_validation(),
+            HydrationPolicy::default().allow_streaming(),
+            HydrationPolicy::default()
+                .require_validation()
+                .allow_platform_dehydration()
+                .allow_full_restart_hydration(),
+            HydrationPolicy::default()
+                .allow_streaming()
+                .allow_platform_dehydration(),
+        ];
+        for policy in policies {
+            assert!(policy.check().is_ok(), "{policy:?}");
+        }
+    }
+
+    #[test]
+    fn hydration_policy_check_rejects_validation_with_streaming() {
+        let err = HydrationPolicy::default()
+            .require_validation()
+            .allow_streaming()
+            .check()
+            .unwrap_err();
+        assert_eq!(err.code(), Foundation::E_INVALIDARG);
+    }
+
+    #[test]
+    fn register_rejects_foreign_root() {
+        let path = env::temp_dir().join("wincs-foreign-root");
+        fs::create_dir_all(&path).unwrap();
+
+        let security_id = SecurityId::current_user().unwrap();
+        let existing = SyncRootIdBuilder::new(U16String::from_str("wincs-test"))
+            .user_security_id(security_id.clone())
+            .account_name(U16String::from_str("existing"))
+            .build();
+        let _registration = Registration::from_sync_root_id(&existing)
+            .register_scoped(&path)
+            .unwrap();
+
+        let foreign = SyncRootIdBuilder::new(U16String::from_str("wincs-test"))
+            .user_security_id(security_id)
+            .account_name(U16String::from_str("foreign"))
+            .build();
+        let err = Registration::from_sync_root_id(&foreign)
+            .register(&path)
+            .unwrap_err();
+        assert_eq!(
+            CloudErrorKind::from_error(&err),
+            Some(CloudErrorKind::AlreadyConnected)
+        );
+        assert!(err
+            .message()
+            .to_string()
+            .contains(&existing.as_hstring().to_string()));
+
+        // registering the same id again updates the existing registration
+        Registration::from_sync_root_id(&existing)
+            .register(&path)
+            .unwrap();
+    }
+
+    #[test]
+    fn icon_from_exe_points_at_current_exe() {
+        let sync_root_id = SyncRootIdBuilder::new(U16String::from_str("wincs-test")).build();
+        let registration = Registration::from_sync_root_id(&sync_root_id)
+            .icon_from_exe(3)
+            .unwrap();
+
+        let mut expected = U16String::from_os_str(env::current_exe().unwrap().as_os_str());
+        expected.push_str(",3");
+        assert_eq!(registration.icon, expected);
+    }
+
+    #[test]
+    fn recycle_bin_uri_rejects_invalid_uri() {
+        let sync_root_id = SyncRootIdBuilder::new(U16String::from_str("wincs-test")).build();
+        let invalid = U16String::from_str("not a uri");
+        assert!(Registration::from_sync_root_id(&sync_root_id)
+            .recycle_bin_uri(&invalid)
+            .is_err());
+
+        let valid = U16String::from_str("https://example.com/recycle-bin");
+        assert!(Registration::from_sync_root_id(&sync_root_id)
+            .recycle_bin_uri(&valid)
+            .is_ok());
+    }
+
+    #[test]
+    fn register_scoped_unregisters_on_drop() {
+        let path = env::temp_dir().join("wincs-scoped");
+        fs::create_dir_all(&path).unwrap();
+
+        let sync_root_id = SyncRootIdBuilder::new(U16String::from_str("wincs-test"))
+            .user_security_id(SecurityId::current_user().unwrap())
+            .account_name(U16String::from_str("scoped"))
+            .build();
+        let registration = Registration::from_sync_root_id(&sync_root_id);
+
+        let guard = registration.register_scoped(&path).unwrap();
+        assert!(sync_root_id.is_registered().unwrap());
+        drop(guard);
+        assert!(!sync_root_id.is_registered().unwrap());
+
+        let leaked = registration.register_scoped(&path).unwrap().leak();
+        assert!(sync_root_id.is_registered().unwrap());
+        leaked.unregister().unwrap();
+    }
+}
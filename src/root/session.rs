@@ -1,8 +1,4 @@
-use std::{
-    ffi::OsString,
-    path::Path,
-    sync::{Arc, Weak},
-};
+use std::{any::Any, ffi::OsString, fmt, num::NonZeroUsize, path::Path, sync::Arc};
 
 use windows::{
     core,
@@ -10,19 +6,29 @@ use windows::{
         Storage::CloudFilters::{self, CfConnectSyncRoot, CF_CONNECT_FLAGS},
         System::{
             Com::{self, CoCreateInstance},
-            Search::{self, ISearchCatalogManager, ISearchManager},
+            Search::{self, ISearchCatalogManager, ISearchCrawlScopeManager, ISearchManager},
         },
     },
 };
 
 use crate::{
-    filter::{self, SyncFilter},
-    root::connect::Connection,
+    buffer_pool::BufferPool,
+    filter::{self, Context, Inflight, PanicHandler, Semaphore, SyncFilter},
+    root::{connect::Connection, watcher::Watcher},
 };
 
 /// A builder to create a new connection for the sync root at the specified path.
-#[derive(Debug, Clone, Copy)]
-pub struct Session(CF_CONNECT_FLAGS);
+#[derive(Clone)]
+pub struct Session {
+    flags: CF_CONNECT_FLAGS,
+    index: bool,
+    panic_handler: Option<PanicHandler>,
+    max_concurrent_callbacks: Option<NonZeroUsize>,
+    buffer_pool: Option<Arc<dyn BufferPool>>,
+    index_error_handler: Option<IndexErrorHandler>,
+}
+
+type IndexErrorHandler = Arc<dyn Fn(&core::Error) + Send + Sync>;
 
 impl Session {
     /// Create a new [Session][crate::Session].
@@ -37,8 +43,77 @@ impl Session {
     /// anti-virus is scanning file system activity on files within the sync root.
     ///
     /// A call to the [FileExt::hydrate][crate::ext::FileExt::hydrate] trait will not be blocked by this flag.
+    ///
+    /// Only implicit hydrations caused by the process that made the connection are blocked, and
+    /// the flag could only be specified when connecting. To hydrate a placeholder from an internal
+    /// operation while the flag is set, call [FileExt::hydrate][crate::ext::FileExt::hydrate]
+    /// explicitly before accessing its contents.
     pub fn block_implicit_hydration(mut self) -> Self {
-        self.0 |= CloudFilters::CF_CONNECT_FLAG_BLOCK_SELF_IMPLICIT_HYDRATION;
+        self.flags |= CloudFilters::CF_CONNECT_FLAG_BLOCK_SELF_IMPLICIT_HYDRATION;
+        self
+    }
+
+    /// Whether or not to add the sync root to the Windows Search index when connecting, enabled
+    /// by default.
+    ///
+    /// Indexing is best-effort, if the Windows Search service is unavailable (e.g. on a headless
+    /// server), the connection proceeds without it and a warning is printed, read
+    /// [Session::index_error_handler][crate::Session::index_error_handler]. Disable it if the
+    /// provider manages indexing itself.
+    pub fn index(mut self, index: bool) -> Self {
+        self.index = index;
+        self
+    }
+
+    /// Calls the handler with the error if the sync root could not be added to the Windows Search
+    /// index when connecting, instead of printing a warning to the standard error stream.
+    ///
+    /// The connection proceeds regardless, read [Session::index][crate::Session::index].
+    pub fn index_error_handler(
+        mut self,
+        handler: impl Fn(&core::Error) + Send + Sync + 'static,
+    ) -> Self {
+        self.index_error_handler = Some(Arc::new(handler));
+        self
+    }
+
+    /// Calls the handler with the payload of a panic that occurred within a
+    /// [SyncFilter][crate::SyncFilter] callback.
+    ///
+    /// Panics are always caught before they could unwind into the operating system, in which
+    /// case the operation is failed with
+    /// [CloudErrorKind::Unsuccessful][crate::CloudErrorKind::Unsuccessful]. The panic message is
+    /// still printed by the panic hook, this handler is useful for additional reporting.
+    pub fn panic_handler(
+        mut self,
+        handler: impl Fn(&(dyn Any + Send)) + Send + Sync + 'static,
+    ) -> Self {
+        self.panic_handler = Some(Arc::new(handler));
+        self
+    }
+
+    /// Limits the amount of [SyncFilter][crate::SyncFilter] callbacks that are handed a ticket
+    /// (e.g. [SyncFilter::fetch_data][crate::SyncFilter::fetch_data]) running at once.
+    ///
+    /// Additional callbacks block until a running callback returns, which is useful for providers
+    /// that must serialize access to the remote. Note that this could increase the latency of
+    /// file operations. Only the callback itself is counted, if its ticket is moved to another
+    /// thread, the operation no longer holds a slot once the callback returns. Notifications, such
+    /// as cancellations, are never blocked.
+    pub fn max_concurrent_callbacks(mut self, max: NonZeroUsize) -> Self {
+        self.max_concurrent_callbacks = Some(max);
+        self
+    }
+
+    /// The [BufferPool][crate::BufferPool] the transfer buffers of
+    /// [FetchData][crate::ticket::FetchData] tickets are taken from, e.g. by
+    /// [FetchData::transfer_from][crate::ticket::FetchData::transfer_from].
+    ///
+    /// By default, no pool is used and every transfer allocates its own buffer, which is freed
+    /// once the transfer completes. Pass a [VecPool][crate::VecPool] to reuse buffers across the
+    /// callbacks of the connection, at the cost of keeping the released buffers in memory.
+    pub fn buffer_pool(mut self, pool: impl BufferPool + 'static) -> Self {
+        self.buffer_pool = Some(Arc::new(pool));
         self
     }
 
@@ -48,50 +123,123 @@ impl Session {
         P: AsRef<Path>,
         T: SyncFilter + 'static,
     {
-        // https://github.com/microsoft/Windows-classic-samples/blob/27ffb0811ca761741502feaefdb591aebf592193/Samples/CloudMirror/CloudMirror/Utilities.cpp#L19
-        index_path(path.as_ref())?;
+        if self.index {
+            // https://github.com/microsoft/Windows-classic-samples/blob/27ffb0811ca761741502feaefdb591aebf592193/Samples/CloudMirror/CloudMirror/Utilities.cpp#L19
+            // the sync root is still usable without being indexed, so a failure isn't fatal
+            if let Err(err) = index_path(path.as_ref()) {
+                match &self.index_error_handler {
+                    Some(handler) => handler(&err),
+                    None => eprintln!(
+                        "warning: failed to index the sync root at {:?}: {}",
+                        path.as_ref(),
+                        err
+                    ),
+                }
+            }
+        }
 
         let filter = Arc::new(filter);
+        let inflight = Arc::new(Inflight::default());
         let callbacks = filter::callbacks::<T>();
+        // store a weak arc so that it could be upgraded when it's being used and when the
+        // connection is closed, the filter could be freed
+        let context = Box::new(Context {
+            filter: Arc::downgrade(&filter),
+            inflight: inflight.clone(),
+            panic_handler: self.panic_handler,
+            limit: self
+                .max_concurrent_callbacks
+                .map(|max| Semaphore::new(max.get())),
+            buffer_pool: self.buffer_pool,
+        });
         unsafe {
             CfConnectSyncRoot(
                 path.as_ref().as_os_str(),
                 callbacks.as_ptr(),
-                // create a weak arc so that it could be upgraded when it's being used and when the
-                // connection is closed, the filter could be freed
-                Weak::into_raw(Arc::downgrade(&filter)) as *const _,
+                // the context is owned by the connection, which frees it once disconnected
+                &*context as *const Context<T> as *const _,
                 // This is enabled by default to remove the Option requirement around various fields of the
                 // [Request][crate::Request] struct
-                self.0
+                self.flags
                     | CloudFilters::CF_CONNECT_FLAG_REQUIRE_FULL_FILE_PATH
                     | CloudFilters::CF_CONNECT_FLAG_REQUIRE_PROCESS_INFO,
             )
         }
-        .map(|key| Connection::new(key.0, callbacks, filter))
+        .map(|key| {
+            let watcher = Watcher::spawn(path.as_ref().to_owned(), Arc::downgrade(&filter));
+            Connection::new(
+                key.0,
+                path.as_ref().to_owned(),
+                callbacks,
+                watcher,
+                inflight,
+                context,
+                filter,
+            )
+        })
     }
 }
 
 impl Default for Session {
     fn default() -> Self {
-        Self(CloudFilters::CF_CONNECT_FLAG_NONE)
+        Self {
+            flags: CloudFilters::CF_CONNECT_FLAG_NONE,
+            index: true,
+            panic_handler: None,
+            max_concurrent_callbacks: None,
+            buffer_pool: None,
+            index_error_handler: None,
+        }
+    }
+}
+
+impl fmt::Debug for Session {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Session")
+            .field("flags", &self.flags)
+            .field("index", &self.index)
+            .field("panic_handler", &self.panic_handler.is_some())
+            .field("max_concurrent_callbacks", &self.max_concurrent_callbacks)
+            .field("buffer_pool", &self.buffer_pool.is_some())
+            .field("index_error_handler", &self.index_error_handler.is_some())
+            .finish()
+    }
+}
+
+/// Removes the sync root at the given path from the Windows Search index, undoing the rule added
+/// by [Session::connect][crate::Session::connect].
+///
+/// This should be called after unregistering the sync root, e.g. when the provider is
+/// uninstalled, otherwise the index configuration is left behind.
+pub fn deindex_path(path: impl AsRef<Path>) -> core::Result<()> {
+    unsafe {
+        let crawler = crawl_scope_manager()?;
+        crawler.RemoveDefaultScopeRule(scope_url(path.as_ref()))?;
+        crawler.SaveAll()
     }
 }
 
 fn index_path(path: &Path) -> core::Result<()> {
     unsafe {
-        let searcher: ISearchManager = CoCreateInstance(
-            &Search::CSearchManager as *const _,
-            None,
-            Com::CLSCTX_SERVER,
-        )?;
+        let crawler = crawl_scope_manager()?;
+        crawler.AddDefaultScopeRule(scope_url(path), true, Search::FF_INDEXCOMPLEXURLS.0 as u32)?;
+        crawler.SaveAll()
+    }
+}
 
-        let catalog: ISearchCatalogManager = searcher.GetCatalog("SystemIndex")?;
+unsafe fn crawl_scope_manager() -> core::Result<ISearchCrawlScopeManager> {
+    let searcher: ISearchManager = CoCreateInstance(
+        &Search::CSearchManager as *const _,
+        None,
+        Com::CLSCTX_SERVER,
+    )?;
 
-        let mut url = OsString::from("file:///");
-        url.push(path);
+    let catalog: ISearchCatalogManager = searcher.GetCatalog("SystemIndex")?;
+    catalog.GetCrawlScopeManager()
+}
 
-        let crawler = catalog.GetCrawlScopeManager()?;
-        crawler.AddDefaultScopeRule(url, true, Search::FF_INDEXCOMPLEXURLS.0 as u32)?;
-        crawler.SaveAll()
-    }
+fn scope_url(path: &Path) -> OsString {
+    let mut url = OsString::from("file:///");
+    url.push(path);
+    url
 }
@@ -1,29 +1,64 @@
-use std::{mem::MaybeUninit, path::Path, ptr};
+use std::{error::Error, ffi::OsString, fmt, mem, os::windows::ffi::OsStringExt, path::Path, ptr};
 
 use widestring::{U16CString, U16Str, U16String};
 use windows::{
     core::{self, HSTRING, PWSTR},
-    Storage::Provider::StorageProviderSyncRootManager,
+    Storage::{
+        Provider::{StorageProviderSyncRootInfo, StorageProviderSyncRootManager},
+        Streams::DataReader,
+    },
     Win32::{
         Foundation::{self, GetLastError, HANDLE},
         Security::{self, Authorization::ConvertSidToStringSidW, GetTokenInformation, TOKEN_USER},
-        Storage::CloudFilters,
+        Storage::CloudFilters::{self, CfGetSyncRootInfoByPath, CF_SYNC_ROOT_BASIC_INFO},
         System::Memory::LocalFree,
     },
 };
 
-use crate::ext::PathExt;
+use crate::{
+    ext::PathExt,
+    platform::{self, Feature},
+    root::register,
+};
 
 /// Returns a list of active sync roots.
-pub fn active_roots() {
-    // GetCurrentSyncRoots()
-    todo!()
+pub fn active_roots() -> core::Result<Vec<SyncRootId>> {
+    let roots = StorageProviderSyncRootManager::GetCurrentSyncRoots()?;
+    (0..roots.Size()?)
+        .map(|index| Ok(SyncRootId(roots.GetAt(index)?.Id()?)))
+        .collect()
+}
+
+/// Unregisters every active sync root registered with the given provider name, returning the
+/// result of unregistering each sync root.
+///
+/// This is useful for cleaning up every account of the provider on uninstall.
+pub fn unregister_provider(
+    provider_name: &U16Str,
+) -> core::Result<Vec<(SyncRootId, core::Result<()>)>> {
+    Ok(active_roots()?
+        .into_iter()
+        .filter(|id| {
+            id.split_components()
+                .is_some_and(|(provider_id, _, _)| provider_id == provider_name)
+        })
+        .map(|id| {
+            let result = id.unregister();
+            (id, result)
+        })
+        .collect())
 }
 
 /// Returns whether or not the Cloud Filter API is supported (or at least the UWP part of it, for
 /// now).
+///
+/// On versions of Windows prior to the introduction of the underlying query, only the build number
+/// is checked, read [platform][crate::platform] for more information.
 pub fn is_supported() -> core::Result<bool> {
-    // TODO: Check current windows version to see if this function is supported before calling it
+    if !platform::supports_feature(Feature::SupportQuery) {
+        return Ok(platform::supports_feature(Feature::CloudFilter));
+    }
+
     StorageProviderSyncRootManager::IsSupported()
 }
 
@@ -39,20 +74,33 @@ impl SyncRootIdBuilder {
     /// Create a new builder with the given provider name.
     ///
     /// The provider name MUST NOT contain exclamation points and it must be within [255](https://docs.microsoft.com/en-us/windows/win32/api/cfapi/ns-cfapi-cf_sync_root_provider_info#remarks) characters.
+    ///
+    /// Panics if the provider name is invalid, read
+    /// [SyncRootIdBuilder::try_new][crate::SyncRootIdBuilder::try_new] for a fallible version.
     pub fn new(provider_name: U16String) -> Self {
-        // TODO: assert that is doesn't have exclamation points
-        assert!(
-            provider_name.len() <= CloudFilters::CF_MAX_PROVIDER_NAME_LENGTH as usize,
-            "provider name must not exceed {} characters, got {} characters",
-            CloudFilters::CF_MAX_PROVIDER_NAME_LENGTH,
-            provider_name.len()
-        );
+        Self::try_new(provider_name).unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    /// Create a new builder with the given provider name, returning an error if the provider name
+    /// is invalid.
+    ///
+    /// This is useful when the provider name comes from user input, such as a configuration file.
+    pub fn try_new(provider_name: U16String) -> Result<Self, InvalidName> {
+        if provider_name.len() > CloudFilters::CF_MAX_PROVIDER_NAME_LENGTH as usize {
+            return Err(InvalidName::TooLong {
+                max: CloudFilters::CF_MAX_PROVIDER_NAME_LENGTH as usize,
+                len: provider_name.len(),
+            });
+        }
+        if provider_name.as_slice().contains(&SyncRootId::SEPARATOR) {
+            return Err(InvalidName::ContainsSeparator);
+        }
 
-        Self {
+        Ok(Self {
             provider_name,
             user_security_id: SecurityId(U16String::new()),
             account_name: U16String::new(),
-        }
+        })
     }
 
     /// The security id of the Windows user. Retrieve this value via the
@@ -118,8 +166,70 @@ impl SyncRootId {
         )
     }
 
+    /// Whether or not the [SyncRootId][crate::SyncRootId] is registered and its path is still a
+    /// sync root.
+    ///
+    /// A registration could go stale, e.g. after a Windows feature update drops the shell
+    /// integration, or when the folder of the sync root is removed. This is useful to check on
+    /// startup, if it returns `false`, the sync root should be registered again.
+    pub fn is_healthy(&self) -> core::Result<bool> {
+        let info = match StorageProviderSyncRootManager::GetSyncRootInformationForId(&self.0) {
+            Ok(info) => info,
+            Err(err) if err.win32_error() == Some(Foundation::ERROR_NOT_FOUND) => return Ok(false),
+            Err(err) => return Err(err),
+        };
+        // the folder of the sync root could have been removed
+        let path = match info.Path().and_then(|folder| folder.Path()) {
+            Ok(path) => OsString::from_wide(path.as_wide()),
+            Err(_) => return Ok(false),
+        };
+
+        let mut basic_info = CF_SYNC_ROOT_BASIC_INFO::default();
+        Ok(unsafe {
+            CfGetSyncRootInfoByPath(
+                path.as_os_str(),
+                CloudFilters::CF_SYNC_ROOT_INFO_BASIC,
+                &mut basic_info as *mut _ as *mut _,
+                mem::size_of::<CF_SYNC_ROOT_BASIC_INFO>() as u32,
+                ptr::null_mut(),
+            )
+        }
+        .is_ok())
+    }
+
+    /// The information of the registered sync root as reported by the operating system.
+    ///
+    /// This reflects the latest registration, including any changes applied by registering the
+    /// same [SyncRootId][crate::SyncRootId] again.
+    pub fn info(&self) -> core::Result<StorageProviderSyncRootInfo> {
+        StorageProviderSyncRootManager::GetSyncRootInformationForId(&self.0)
+    }
+
+    /// The byte slice assigned to the sync root on registration.
+    ///
+    /// This is the same value as [Request::register_blob][crate::Request::register_blob], although
+    /// it could be read outside of callbacks.
+    pub fn blob(&self) -> core::Result<Vec<u8>> {
+        let buffer = match self.info()?.Context() {
+            Ok(buffer) => buffer,
+            // the sync root was registered without a blob, a null interface is returned as an
+            // error with a success code
+            Err(err) if err.code().is_ok() => return Ok(Vec::new()),
+            Err(err) => return Err(err),
+        };
+
+        let mut blob = vec![0; buffer.Length()? as usize];
+        DataReader::FromBuffer(buffer)?.ReadBytes(&mut blob)?;
+
+        Ok(blob)
+    }
+
     /// Unregisters the sync root at the current [SyncRootId][crate::SyncRootId] if it exists.
+    ///
+    /// The sync root is not removed from the Windows Search index, read
+    /// [deindex_path][crate::deindex_path].
     pub fn unregister(&self) -> core::Result<()> {
+        register::remove_shell_handlers(self);
         StorageProviderSyncRootManager::Unregister(&self.0)
     }
 
@@ -137,28 +247,73 @@ impl SyncRootId {
     ///
     /// The order goes as follows:
     /// `(provider-id, security-id, account-name)`
-    // TODO: This doesn't work properly, it forgets to include the account name
+    ///
+    /// # Panics
+    /// Panics if the [SyncRootId][crate::SyncRootId] is malformed. Use
+    /// [SyncRootId::try_components][crate::SyncRootId::try_components] to handle the error instead.
     pub fn to_components(&self) -> (&U16Str, &U16Str, &U16Str) {
-        let mut components = Vec::with_capacity(3);
-        let mut bytes = self.0.as_wide();
-
-        for index in 0..2 {
-            match bytes.iter().position(|&byte| byte == Self::SEPARATOR) {
-                Some(position) => {
-                    components.insert(index, U16Str::from_slice(bytes));
-                    bytes = &bytes[(position + 1)..];
-                }
-                None => {
-                    // TODO: return a result instead of panic
-                    panic!("malformed sync root id, got {:?}", components)
-                }
-            }
+        self.split_components()
+            .unwrap_or_else(|| panic!("malformed sync root id, got {:?}", self.as_u16str()))
+    }
+
+    /// The three components of a [SyncRootId][crate::SyncRootId] as owned strings, read
+    /// [SyncRootId::to_components][crate::SyncRootId::to_components] for more information.
+    ///
+    /// Invalid UTF-16 is replaced with the unicode replacement character. An error is returned if
+    /// the [SyncRootId][crate::SyncRootId] is malformed.
+    pub fn try_components(&self) -> core::Result<(OsString, OsString, OsString)> {
+        let (provider_id, security_id, account_name) =
+            self.split_components().ok_or_else(|| {
+                core::Error::new(
+                    Foundation::E_INVALIDARG,
+                    format!("malformed sync root id, got {:?}", self.as_u16str()).into(),
+                )
+            })?;
+
+        Ok((
+            provider_id.to_string_lossy().into(),
+            security_id.to_string_lossy().into(),
+            account_name.to_string_lossy().into(),
+        ))
+    }
+
+    fn split_components(&self) -> Option<(&U16Str, &U16Str, &U16Str)> {
+        let mut components = self.0.as_wide().splitn(3, |&byte| byte == Self::SEPARATOR);
+        match (components.next(), components.next(), components.next()) {
+            (Some(provider_id), Some(security_id), Some(account_name)) => Some((
+                U16Str::from_slice(provider_id),
+                U16Str::from_slice(security_id),
+                U16Str::from_slice(account_name),
+            )),
+            _ => None,
         }
+    }
+}
 
-        (components[0], components[1], components[2])
+/// An error returned when a component of a [SyncRootId][crate::SyncRootId] is invalid.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InvalidName {
+    /// The name exceeds the maximum amount of characters.
+    TooLong { max: usize, len: usize },
+    /// The name contains an exclamation point, which separates the components of a
+    /// [SyncRootId][crate::SyncRootId].
+    ContainsSeparator,
+}
+
+impl fmt::Display for InvalidName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InvalidName::TooLong { max, len } => write!(
+                f,
+                "provider name must not exceed {max} characters, got {len} characters"
+            ),
+            InvalidName::ContainsSeparator => write!(f, "name must not contain exclamation points"),
+        }
     }
 }
 
+impl Error for InvalidName {}
+
 /// A user security id (SID).
 #[derive(Debug, Clone)]
 pub struct SecurityId(U16String);
@@ -167,6 +322,24 @@ impl SecurityId {
     // https://docs.microsoft.com/en-us/windows/win32/api/processthreadsapi/nf-processthreadsapi-getcurrentthreadeffectivetoken
     const CURRENT_THREAD_EFFECTIVE_TOKEN: HANDLE = HANDLE(-6);
 
+    /// Creates a new [SecurityId][crate::SecurityId].
+    ///
+    /// Panics if the id contains an exclamation point, read
+    /// [SecurityId::try_new][crate::SecurityId::try_new] for a fallible version.
+    pub fn new(id: U16String) -> Self {
+        Self::try_new(id).unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    /// Creates a new [SecurityId][crate::SecurityId], returning an error if the id contains an
+    /// exclamation point.
+    pub fn try_new(id: U16String) -> Result<Self, InvalidName> {
+        if id.as_slice().contains(&SyncRootId::SEPARATOR) {
+            return Err(InvalidName::ContainsSeparator);
+        }
+
+        Ok(Self(id))
+    }
+
     /// Creates a new [SecurityId][crate::SecurityId] without any assertions.
     pub fn new_unchecked(id: U16String) -> Self {
         Self(id)
@@ -174,31 +347,41 @@ impl SecurityId {
 
     /// The [SecurityId][crate::SecurityId] for the logged in user.
     pub fn current_user() -> core::Result<Self> {
+        Self::from_token(Self::CURRENT_THREAD_EFFECTIVE_TOKEN)
+    }
+
+    /// The [SecurityId][crate::SecurityId] for the user of the given access token, e.g. to
+    /// register a sync root on behalf of another logged in user from a service.
+    ///
+    /// The token must have `TOKEN_QUERY` access.
+    pub fn from_token(token: HANDLE) -> core::Result<Self> {
         unsafe {
             let mut token_size = 0;
-            let mut token = MaybeUninit::<TOKEN_USER>::uninit();
-
             if !GetTokenInformation(
-                Self::CURRENT_THREAD_EFFECTIVE_TOKEN,
+                token,
                 Security::TokenUser,
                 ptr::null_mut(),
                 0,
                 &mut token_size,
             )
             .as_bool()
-                && GetLastError() == Foundation::ERROR_INSUFFICIENT_BUFFER
+                && GetLastError() != Foundation::ERROR_INSUFFICIENT_BUFFER
             {
-                GetTokenInformation(
-                    Self::CURRENT_THREAD_EFFECTIVE_TOKEN,
-                    Security::TokenUser,
-                    &mut token as *mut _ as *mut _,
-                    token_size,
-                    &mut token_size,
-                )
-                .ok()?;
+                return Err(core::Error::from_win32());
             }
 
-            let token = token.assume_init();
+            // the sid is stored directly after the struct, so the buffer is larger than it
+            let mut buffer = vec![0u64; (token_size as usize).div_ceil(mem::size_of::<u64>())];
+            GetTokenInformation(
+                token,
+                Security::TokenUser,
+                buffer.as_mut_ptr() as *mut _,
+                token_size,
+                &mut token_size,
+            )
+            .ok()?;
+
+            let token = &*(buffer.as_ptr() as *const TOKEN_USER);
             let mut sid = PWSTR(ptr::null_mut());
             ConvertSidToStringSidW(token.User.Sid, &mut sid as *mut _).ok()?;
 
@@ -209,3 +392,186 @@ impl SecurityId {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs};
+
+    use super::*;
+    use crate::Registration;
+
+    #[test]
+    fn try_components_splits_id() {
+        let id = SyncRootIdBuilder::new(U16String::from_str("provider"))
+            .user_security_id(SecurityId::new(U16String::from_str("S-1-5-21")))
+            .account_name(U16String::from_str("account"))
+            .build();
+        assert_eq!(
+            id.try_components().unwrap(),
+            ("provider".into(), "S-1-5-21".into(), "account".into())
+        );
+    }
+
+    #[test]
+    fn try_components_keeps_separator_in_account_name() {
+        let id = SyncRootId(HSTRING::from("provider!S-1-5-21!first!second"));
+        assert_eq!(
+            id.try_components().unwrap(),
+            ("provider".into(), "S-1-5-21".into(), "first!second".into())
+        );
+    }
+
+    #[test]
+    fn try_components_allows_empty_components() {
+        let id = SyncRootIdBuilder::new(U16String::from_str("provider")).build();
+        assert_eq!(
+            id.try_components().unwrap(),
+            ("provider".into(), OsString::new(), OsString::new())
+        );
+    }
+
+    #[test]
+    fn try_components_rejects_malformed_id() {
+        for id in ["provider", "provider!S-1-5-21"] {
+            let err = SyncRootId(HSTRING::from(id)).try_components().unwrap_err();
+            assert_eq!(err.code(), Foundation::E_INVALIDARG);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "malformed sync root id")]
+    fn to_components_panics_on_malformed_id() {
+        SyncRootId(HSTRING::from("provider")).to_components();
+    }
+
+    #[test]
+    fn builder_try_new_accepts_valid_name() {
+        let name =
+            U16String::from_str(&"a".repeat(CloudFilters::CF_MAX_PROVIDER_NAME_LENGTH as usize));
+        assert!(SyncRootIdBuilder::try_new(name).is_ok());
+    }
+
+    #[test]
+    fn builder_try_new_rejects_long_name() {
+        let max = CloudFilters::CF_MAX_PROVIDER_NAME_LENGTH as usize;
+        let err =
+            SyncRootIdBuilder::try_new(U16String::from_str(&"a".repeat(max + 1))).unwrap_err();
+        assert_eq!(err, InvalidName::TooLong { max, len: max + 1 });
+    }
+
+    #[test]
+    fn builder_try_new_rejects_separator() {
+        let err = SyncRootIdBuilder::try_new(U16String::from_str("provider!name")).unwrap_err();
+        assert_eq!(err, InvalidName::ContainsSeparator);
+    }
+
+    #[test]
+    #[should_panic(expected = "name must not contain exclamation points")]
+    fn builder_new_panics_on_invalid_name() {
+        SyncRootIdBuilder::new(U16String::from_str("provider!name"));
+    }
+
+    #[test]
+    fn security_id_try_new() {
+        assert!(SecurityId::try_new(U16String::from_str("S-1-5-21")).is_ok());
+        assert_eq!(
+            SecurityId::try_new(U16String::from_str("S-1!5")).unwrap_err(),
+            InvalidName::ContainsSeparator
+        );
+    }
+
+    #[test]
+    fn invalid_name_display() {
+        assert_eq!(
+            InvalidName::TooLong { max: 255, len: 256 }.to_string(),
+            "provider name must not exceed 255 characters, got 256 characters"
+        );
+        assert_eq!(
+            InvalidName::ContainsSeparator.to_string(),
+            "name must not contain exclamation points"
+        );
+    }
+
+    #[test]
+    fn from_token_matches_current_user() {
+        // https://docs.microsoft.com/en-us/windows/win32/api/processthreadsapi/nf-processthreadsapi-getcurrentprocesstoken
+        const CURRENT_PROCESS_TOKEN: HANDLE = HANDLE(-4);
+
+        let security_id = SecurityId::from_token(CURRENT_PROCESS_TOKEN).unwrap();
+        assert!(security_id.0.to_string_lossy().starts_with("S-1-"));
+        assert_eq!(security_id.0, SecurityId::current_user().unwrap().0);
+    }
+
+    #[test]
+    fn is_healthy_after_removing_root() {
+        let path = env::temp_dir().join("wincs-healthy");
+        fs::create_dir_all(&path).unwrap();
+
+        let sync_root_id = SyncRootIdBuilder::new(U16String::from_str("wincs-test"))
+            .user_security_id(SecurityId::current_user().unwrap())
+            .account_name(U16String::from_str("healthy"))
+            .build();
+        let _registration = Registration::from_sync_root_id(&sync_root_id)
+            .register_scoped(&path)
+            .unwrap();
+        assert!(sync_root_id.is_healthy().unwrap());
+
+        fs::remove_dir_all(&path).unwrap();
+        assert!(!sync_root_id.is_healthy().unwrap());
+    }
+
+    #[test]
+    fn unregister_provider_removes_every_root() {
+        let provider_name = U16String::from_str("wincs-test-unregister");
+        let security_id = SecurityId::current_user().unwrap();
+        let ids = ["first", "second"].map(|account_name| {
+            let path = env::temp_dir().join(format!("wincs-unregister-{account_name}"));
+            fs::create_dir_all(&path).unwrap();
+
+            let sync_root_id = SyncRootIdBuilder::new(provider_name.clone())
+                .user_security_id(security_id.clone())
+                .account_name(U16String::from_str(account_name))
+                .build();
+            Registration::from_sync_root_id(&sync_root_id)
+                .register_scoped(&path)
+                .unwrap()
+                .leak()
+        });
+
+        let results = unregister_provider(&provider_name).unwrap();
+        for id in &ids {
+            let (_, result) = results
+                .iter()
+                .find(|(unregistered, _)| unregistered.as_u16str() == id.as_u16str())
+                .unwrap();
+            assert!(result.is_ok(), "{result:?}");
+        }
+
+        let active = active_roots().unwrap();
+        assert!(!active
+            .iter()
+            .any(|active| ids.iter().any(|id| active.as_u16str() == id.as_u16str())));
+    }
+
+    #[test]
+    fn blob_round_trip() {
+        let security_id = SecurityId::current_user().unwrap();
+        let blob = (0..1024).map(|i| i as u8).collect::<Vec<_>>();
+        for (account_name, blob) in [("blob", Some(&blob[..])), ("no-blob", None)] {
+            let path = env::temp_dir().join(format!("wincs-{account_name}"));
+            fs::create_dir_all(&path).unwrap();
+
+            let sync_root_id = SyncRootIdBuilder::new(U16String::from_str("wincs-test"))
+                .user_security_id(security_id.clone())
+                .account_name(U16String::from_str(account_name))
+                .build();
+            let mut registration = Registration::from_sync_root_id(&sync_root_id);
+            if let Some(blob) = blob {
+                registration = registration.blob(blob);
+            }
+            let _registration = registration.register_scoped(&path).unwrap();
+
+            assert_eq!(sync_root_id.blob().unwrap(), blob.unwrap_or_default());
+        }
+    }
+}
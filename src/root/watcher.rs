@@ -0,0 +1,115 @@
+use std::{
+    mem::{self, MaybeUninit},
+    path::{Path, PathBuf},
+    ptr,
+    sync::{
+        mpsc::{self, RecvTimeoutError, Sender},
+        Weak,
+    },
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use windows::{
+    core,
+    Win32::Storage::CloudFilters::{
+        CfGetSyncRootInfoByPath, CF_SYNC_ROOT_INFO_PROVIDER, CF_SYNC_ROOT_PROVIDER_INFO,
+    },
+};
+
+use crate::{ext::ProviderStatus, filter::SyncFilter};
+
+/// How often the provider status of the sync root is queried.
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Polls the sync root from a background thread, notifying the [SyncFilter][crate::SyncFilter]
+/// of any changes.
+///
+/// The thread exits once the [Watcher] is dropped or the filter is freed. Dropping the [Watcher]
+/// waits for the thread to exit, so
+/// [SyncFilter::watcher_stopped][crate::SyncFilter::watcher_stopped] has been called by the time
+/// the [Connection][crate::Connection] is dropped.
+#[derive(Debug)]
+pub struct Watcher {
+    shutdown: Option<Sender<()>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Watcher {
+    pub fn spawn<T: SyncFilter + 'static>(path: PathBuf, filter: Weak<T>) -> Self {
+        let (shutdown, receiver) = mpsc::channel();
+
+        let thread = thread::spawn(move || {
+            match filter.upgrade() {
+                Some(filter) => filter.watcher_started(),
+                None => return,
+            }
+            // also notifies if the thread dies from a panicking callback
+            let _stopped = Stopped(&filter);
+
+            let mut last_status = provider_status(&path).ok();
+            while let Err(RecvTimeoutError::Timeout) = receiver.recv_timeout(POLL_INTERVAL) {
+                let status = match provider_status(&path) {
+                    Ok(status) => status,
+                    Err(_) => continue,
+                };
+
+                // the first status read is only a baseline, only transitions are reported
+                let changed = matches!(last_status, Some(last) if last != status);
+                last_status = Some(status);
+
+                if changed {
+                    match filter.upgrade() {
+                        Some(filter) => filter.status_changed(status),
+                        None => break,
+                    }
+                }
+            }
+        });
+
+        Self {
+            shutdown: Some(shutdown),
+            thread: Some(thread),
+        }
+    }
+}
+
+/// Calls [SyncFilter::watcher_stopped][crate::SyncFilter::watcher_stopped] when dropped.
+struct Stopped<'a, T: SyncFilter>(&'a Weak<T>);
+
+impl<T: SyncFilter> Drop for Stopped<'_, T> {
+    fn drop(&mut self) {
+        // the filter is only gone if the connection was dropped, in which case there is no one
+        // left to notify
+        if let Some(filter) = self.0.upgrade() {
+            filter.watcher_stopped();
+        }
+    }
+}
+
+impl Drop for Watcher {
+    fn drop(&mut self) {
+        // dropping the sender wakes the thread up
+        self.shutdown.take();
+        if let Some(thread) = self.thread.take() {
+            // the watcher could be dropped from the thread itself within a callback
+            if thread.thread().id() != thread::current().id() {
+                let _ = thread.join();
+            }
+        }
+    }
+}
+
+pub fn provider_status(path: &Path) -> core::Result<ProviderStatus> {
+    let mut info = MaybeUninit::<CF_SYNC_ROOT_PROVIDER_INFO>::uninit();
+    unsafe {
+        CfGetSyncRootInfoByPath(
+            path.as_os_str(),
+            CF_SYNC_ROOT_INFO_PROVIDER,
+            info.as_mut_ptr() as *mut _,
+            mem::size_of::<CF_SYNC_ROOT_PROVIDER_INFO>() as u32,
+            ptr::null_mut(),
+        )
+        .map(|_| info.assume_init().ProviderStatus.into())
+    }
+}
@@ -0,0 +1,206 @@
+use std::{
+    collections::{BTreeSet, HashMap},
+    fs,
+    io::{Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use windows::core;
+
+use crate::{
+    error::CloudErrorKind,
+    filter::{info, ticket, SyncFilter},
+    placeholder_file::{Metadata, PlaceholderFile},
+    request::Request,
+    root::{Connection, HydrationType, Registration, Session, SyncRootId, SyncRootRegistration},
+    utility::io_to_core,
+};
+
+/// A [SyncFilter][crate::SyncFilter] serving a tree of files from memory.
+///
+/// Files are keyed by their path relative to the sync root, directories are inferred from the
+/// file paths. This is useful for validating an integration against a real sync root without
+/// implementing a remote.
+///
+/// ```no_run
+/// use std::{collections::HashMap, fs, path::PathBuf};
+///
+/// use widestring::U16String;
+/// use wincs::{testing::MemoryFilter, SecurityId, SyncRootIdBuilder};
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let sync_root_id = SyncRootIdBuilder::new(U16String::from_str("wincs-testing"))
+///     .user_security_id(SecurityId::current_user()?)
+///     .build();
+/// let files = HashMap::from([(PathBuf::from("dir\\file.txt"), b"hello".to_vec())]);
+///
+/// let filter = MemoryFilter::new("C:\\SyncRoot", files);
+/// let _registration = filter.register(&sync_root_id)?;
+/// let connection = filter.connect()?;
+///
+/// // reading the placeholder hydrates it from memory
+/// assert_eq!(fs::read("C:\\SyncRoot\\dir\\file.txt")?, b"hello");
+///
+/// // deletes are not approved by the filter, so the placeholders are removed once disconnected
+/// let filter = connection.filter().clone();
+/// connection.disconnect()?;
+/// filter.cleanup()?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct MemoryFilter {
+    root: PathBuf,
+    files: HashMap<PathBuf, Vec<u8>>,
+}
+
+impl MemoryFilter {
+    /// Creates a new [MemoryFilter][crate::testing::MemoryFilter] serving the files within the
+    /// sync root at `root`.
+    pub fn new(root: impl Into<PathBuf>, files: HashMap<PathBuf, Vec<u8>>) -> Self {
+        Self {
+            root: root.into(),
+            files,
+        }
+    }
+
+    /// The path to the sync root.
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// The files served by the filter, keyed by their path relative to the sync root.
+    pub fn files(&self) -> &HashMap<PathBuf, Vec<u8>> {
+        &self.files
+    }
+
+    /// Registers the sync root, returning a guard that unregisters it when dropped.
+    pub fn register(&self, sync_root_id: &SyncRootId) -> core::Result<SyncRootRegistration> {
+        Registration::from_sync_root_id(sync_root_id)
+            .hydration_type(HydrationType::Full)
+            .register_scoped(&self.root)
+    }
+
+    /// Connects the filter to the sync root.
+    pub fn connect(self) -> core::Result<Connection<Arc<Self>>> {
+        Session::new().connect(self.root.clone(), self)
+    }
+
+    /// Removes every placeholder within the sync root whilst keeping the sync root itself intact.
+    ///
+    /// The filter does not approve deletes, so this should be called once the sync root is
+    /// disconnected.
+    pub fn cleanup(&self) -> core::Result<()> {
+        for entry in fs::read_dir(&self.root).map_err(io_to_core)? {
+            let entry = entry.map_err(io_to_core)?;
+            if entry.file_type().map_err(io_to_core)?.is_dir() {
+                fs::remove_dir_all(entry.path())
+            } else {
+                fs::remove_file(entry.path())
+            }
+            .map_err(io_to_core)?;
+        }
+
+        Ok(())
+    }
+
+    fn relative_path(&self, request: &Request) -> Option<PathBuf> {
+        request.relative_path(&self.root)
+    }
+}
+
+impl SyncFilter for MemoryFilter {
+    fn fetch_data(&self, request: Request, ticket: ticket::FetchData, info: info::FetchData) {
+        let data = match self
+            .relative_path(&request)
+            .and_then(|path| self.files.get(&path))
+        {
+            Some(data) => data,
+            None => {
+                #[allow(unused_must_use)]
+                {
+                    ticket.fail(CloudErrorKind::InvalidRequest);
+                }
+                return;
+            }
+        };
+
+        // writes must end on a 4KiB boundary or at the end of the file, so write the rest of the
+        // file starting from the required range
+        let start = info.required_file_range().start;
+        let mut placeholder = request.placeholder();
+        if placeholder
+            .seek(SeekFrom::Start(start))
+            .and_then(|_| placeholder.write_all(data.get(start as usize..).unwrap_or_default()))
+            .is_err()
+        {
+            #[allow(unused_must_use)]
+            {
+                ticket.fail(CloudErrorKind::Unsuccessful);
+            }
+        }
+    }
+
+    fn fetch_placeholders(
+        &self,
+        request: Request,
+        ticket: ticket::FetchPlaceholders,
+        _info: info::FetchPlaceholders,
+    ) {
+        let directory = match self.relative_path(&request) {
+            Some(directory) => directory,
+            None => {
+                #[allow(unused_must_use)]
+                {
+                    ticket.fail(CloudErrorKind::InvalidRequest);
+                }
+                return;
+            }
+        };
+
+        let mut placeholders = Vec::new();
+        let mut directories = BTreeSet::new();
+        for (path, data) in &self.files {
+            let mut components = match path.strip_prefix(&directory) {
+                Ok(path) => path.components(),
+                Err(_) => continue,
+            };
+
+            match (components.next(), components.next()) {
+                (Some(name), None) => {
+                    placeholders.push(PlaceholderFile::new(name).map(|placeholder| {
+                        placeholder
+                            .metadata(Metadata::file().size(data.len() as u64))
+                            .mark_sync()
+                    }))
+                }
+                (Some(name), Some(_)) => {
+                    directories.insert(name.as_os_str().to_owned());
+                }
+                _ => {}
+            }
+        }
+
+        placeholders.extend(directories.into_iter().map(|name| {
+            PlaceholderFile::new(name)
+                .map(|placeholder| placeholder.metadata(Metadata::directory()).mark_sync())
+        }));
+
+        let mut placeholders = match placeholders.into_iter().collect::<core::Result<Vec<_>>>() {
+            Ok(placeholders) => placeholders,
+            Err(_) => {
+                #[allow(unused_must_use)]
+                {
+                    ticket.fail(CloudErrorKind::InvalidRequest);
+                }
+                return;
+            }
+        };
+
+        #[allow(unused_must_use)]
+        {
+            ticket.pass_with_placeholder(&mut placeholders);
+        }
+    }
+}
@@ -1,4 +1,17 @@
-use windows::core::HSTRING;
+use std::{
+    io, iter,
+    ops::{Bound, Range, RangeBounds},
+};
+
+use windows::{
+    core::{self, HRESULT, HSTRING},
+    Win32::{
+        Foundation::{E_FAIL, E_INVALIDARG, WIN32_ERROR},
+        Storage::CloudFilters::CF_FILE_RANGE,
+    },
+};
+
+use crate::platform;
 
 // TODO: add something to convert an Option<T> to a *const T and *mut T
 
@@ -13,3 +26,349 @@ where
 }
 
 impl<T: AsRef<[u16]>> ToHString for T {}
+
+/// Converts an [io::Error][std::io::Error] to a [core::Error][windows::core::Error], preserving
+/// the OS error code if there is one.
+pub fn io_to_core(error: io::Error) -> core::Error {
+    match error.raw_os_error() {
+        Some(code) => HRESULT::from(WIN32_ERROR(code as u32)).into(),
+        None => core::Error::new(E_FAIL, error.to_string().into()),
+    }
+}
+
+/// Splits a range into consecutive chunks of at most `chunk` bytes, as required when transferring
+/// data to a placeholder.
+///
+/// The start of the range must be aligned to
+/// [transfer_alignment][crate::platform::transfer_alignment]. The end of the range is rounded up
+/// to the next aligned boundary and clamped to `logical_len`, so the final chunk is only unaligned
+/// if it ends on the logical size of the file.
+pub fn aligned_chunks(
+    range: Range<u64>,
+    chunk: usize,
+    logical_len: u64,
+) -> impl Iterator<Item = Range<u64>> {
+    let alignment = platform::transfer_alignment() as u64;
+
+    assert!(
+        chunk != 0 && chunk.is_multiple_of(alignment as usize),
+        "the chunk size must be a non-zero multiple of {alignment} bytes, got {chunk} bytes"
+    );
+    assert!(
+        range.start.is_multiple_of(alignment),
+        "the start of the range must be aligned to {alignment} bytes, got {}",
+        range.start
+    );
+
+    let end = range
+        .end
+        .checked_next_multiple_of(alignment)
+        .unwrap_or(u64::MAX)
+        .min(logical_len);
+    let mut position = range.start;
+    iter::from_fn(move || {
+        (position < end).then(|| {
+            let start = position;
+            position = position.saturating_add(chunk as u64).min(end);
+            start..position
+        })
+    })
+}
+
+/// The parts of `range` not covered by any of the `present` ranges, widened to the transfer
+/// alignment so they could be transferred to a placeholder.
+///
+/// The start of each gap is rounded down and its end rounded up, clamped to the end of `range`.
+/// Gaps that overlap once aligned are merged.
+pub fn aligned_gaps(
+    range: Range<u64>,
+    present: impl IntoIterator<Item = Range<u64>>,
+) -> Vec<Range<u64>> {
+    let alignment = platform::transfer_alignment() as u64;
+    let mut present = present.into_iter().collect::<Vec<_>>();
+    present.sort_unstable_by_key(|range| range.start);
+
+    let mut gaps: Vec<Range<u64>> = Vec::new();
+    let mut push = |gap: Range<u64>| {
+        let start = gap.start - gap.start % alignment;
+        let end = gap
+            .end
+            .checked_next_multiple_of(alignment)
+            .unwrap_or(u64::MAX)
+            .min(range.end);
+        match gaps.last_mut() {
+            Some(last) if last.end >= start => last.end = last.end.max(end),
+            _ => gaps.push(start..end),
+        }
+    };
+
+    let mut position = range.start;
+    for present in present {
+        if present.start > position {
+            push(position..present.start.min(range.end));
+        }
+        position = position.max(present.end);
+        if position >= range.end {
+            break;
+        }
+    }
+    if position < range.end {
+        push(position..range.end);
+    }
+
+    gaps
+}
+
+/// A range of a file in the form of an offset and length, as expected by the operating system.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FileRange {
+    pub offset: i64,
+    pub length: i64,
+}
+
+impl FileRange {
+    /// Returns `E_INVALIDARG` if the range is reversed or does not fit within a signed 64-bit
+    /// integer.
+    pub fn new(range: Range<u64>) -> core::Result<Self> {
+        if range.start > range.end {
+            return Err(invalid_range(format!(
+                "the start of the range must not exceed its end, got {range:?}"
+            )));
+        }
+        if range.end > i64::MAX as u64 {
+            return Err(invalid_range(format!(
+                "the end of the range must not exceed {}, got {}",
+                i64::MAX,
+                range.end
+            )));
+        }
+
+        Ok(Self {
+            offset: range.start as i64,
+            length: (range.end - range.start) as i64,
+        })
+    }
+
+    /// An unbounded end is represented by a length of -1, which the operating system treats as
+    /// the rest of the file.
+    pub fn from_bounds(range: impl RangeBounds<u64>) -> core::Result<Self> {
+        let start = match range.start_bound() {
+            Bound::Included(x) => *x,
+            Bound::Excluded(x) => x
+                .checked_add(1)
+                .ok_or_else(|| invalid_range("the start of the range overflows".to_owned()))?,
+            Bound::Unbounded => 0,
+        };
+        match range.end_bound() {
+            Bound::Included(x) => Self::new(
+                start
+                    ..x.checked_add(1).ok_or_else(|| {
+                        invalid_range("the end of the range overflows".to_owned())
+                    })?,
+            ),
+            Bound::Excluded(x) => Self::new(start..*x),
+            Bound::Unbounded => Ok(Self {
+                length: -1,
+                ..Self::new(start..start)?
+            }),
+        }
+    }
+}
+
+fn invalid_range(message: String) -> core::Error {
+    core::Error::new(E_INVALIDARG, message.into())
+}
+
+impl From<FileRange> for CF_FILE_RANGE {
+    fn from(range: FileRange) -> Self {
+        Self {
+            StartingOffset: range.offset,
+            Length: range.length,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn aligned_chunks_splits_aligned_range() {
+        let a = platform::transfer_alignment() as u64;
+        let chunks = aligned_chunks(0..4 * a, 2 * a as usize, u64::MAX).collect::<Vec<_>>();
+        assert_eq!(chunks, [0..2 * a, 2 * a..4 * a]);
+    }
+
+    #[test]
+    fn aligned_chunks_keeps_short_final_chunk() {
+        let a = platform::transfer_alignment() as u64;
+        let chunks = aligned_chunks(a..4 * a, 2 * a as usize, u64::MAX).collect::<Vec<_>>();
+        assert_eq!(chunks, [a..3 * a, 3 * a..4 * a]);
+    }
+
+    #[test]
+    fn aligned_chunks_rounds_unaligned_end_up() {
+        let a = platform::transfer_alignment() as u64;
+        let chunks = aligned_chunks(0..a + 1, a as usize, u64::MAX).collect::<Vec<_>>();
+        assert_eq!(chunks, [0..a, a..2 * a]);
+    }
+
+    #[test]
+    fn aligned_chunks_ends_at_eof() {
+        let a = platform::transfer_alignment() as u64;
+        let chunks = aligned_chunks(0..a + 1, a as usize, a + 10).collect::<Vec<_>>();
+        assert_eq!(chunks, [0..a, a..a + 10]);
+    }
+
+    #[test]
+    fn aligned_chunks_stops_at_eof_before_range_end() {
+        let a = platform::transfer_alignment() as u64;
+        let mut chunks = aligned_chunks(0..4 * a, 4 * a as usize, 10);
+        assert_eq!(chunks.next(), Some(0..10));
+        assert_eq!(chunks.next(), None);
+    }
+
+    #[test]
+    fn aligned_chunks_empty_range() {
+        let a = platform::transfer_alignment() as u64;
+        assert_eq!(aligned_chunks(a..a, a as usize, u64::MAX).count(), 0);
+    }
+
+    #[test]
+    #[should_panic]
+    fn aligned_chunks_rejects_unaligned_start() {
+        let a = platform::transfer_alignment() as u64;
+        let _ = aligned_chunks(1..a, a as usize, u64::MAX);
+    }
+
+    #[test]
+    #[should_panic]
+    fn aligned_chunks_rejects_unaligned_chunk() {
+        let a = platform::transfer_alignment() as u64;
+        let _ = aligned_chunks(0..a, a as usize + 1, u64::MAX);
+    }
+
+    #[test]
+    fn aligned_gaps_nothing_present() {
+        let a = platform::transfer_alignment() as u64;
+        assert_eq!(aligned_gaps(0..4 * a, []), vec![0..4 * a]);
+    }
+
+    #[test]
+    fn aligned_gaps_everything_present() {
+        let a = platform::transfer_alignment() as u64;
+        assert!(aligned_gaps(a..3 * a, iter::once(0..4 * a)).is_empty());
+    }
+
+    #[test]
+    fn aligned_gaps_widens_unaligned_gap() {
+        let a = platform::transfer_alignment() as u64;
+        let gaps = aligned_gaps(0..4 * a, [0..a + 10, 2 * a + 10..4 * a]);
+        assert_eq!(gaps, vec![a..3 * a]);
+    }
+
+    #[test]
+    fn aligned_gaps_merges_overlapping_gaps() {
+        let a = platform::transfer_alignment() as u64;
+        let gaps = aligned_gaps(0..4 * a, iter::once(a + 10..a + 20));
+        assert_eq!(gaps, vec![0..4 * a]);
+    }
+
+    #[test]
+    fn aligned_gaps_sorts_present_ranges() {
+        let a = platform::transfer_alignment() as u64;
+        let gaps = aligned_gaps(0..6 * a, [4 * a..5 * a, a..2 * a]);
+        assert_eq!(gaps, vec![0..a, 2 * a..4 * a, 5 * a..6 * a]);
+    }
+
+    #[test]
+    fn aligned_gaps_clamps_to_unaligned_end() {
+        let a = platform::transfer_alignment() as u64;
+        let gaps = aligned_gaps(0..a + 10, iter::once(0..a));
+        assert_eq!(gaps, vec![a..a + 10]);
+    }
+
+    #[test]
+    fn file_range_from_exclusive_range() {
+        assert_eq!(
+            FileRange::new(0..100).unwrap(),
+            FileRange {
+                offset: 0,
+                length: 100
+            }
+        );
+    }
+
+    #[test]
+    fn file_range_rejects_reversed_range() {
+        #[allow(clippy::reversed_empty_ranges)]
+        let range = 100..0;
+        assert_eq!(FileRange::new(range).unwrap_err().code(), E_INVALIDARG);
+    }
+
+    #[test]
+    fn file_range_rejects_end_past_i64() {
+        assert_eq!(
+            FileRange::new(0..i64::MAX as u64 + 1).unwrap_err().code(),
+            E_INVALIDARG
+        );
+        assert!(FileRange::new(0..i64::MAX as u64).is_ok());
+    }
+
+    #[test]
+    fn file_range_from_bounds() {
+        assert_eq!(
+            FileRange::from_bounds(10..=19).unwrap(),
+            FileRange {
+                offset: 10,
+                length: 10
+            }
+        );
+        assert_eq!(
+            FileRange::from_bounds(..100).unwrap(),
+            FileRange {
+                offset: 0,
+                length: 100
+            }
+        );
+        assert_eq!(
+            FileRange::from_bounds((Bound::Excluded(9), Bound::Excluded(20))).unwrap(),
+            FileRange {
+                offset: 10,
+                length: 10
+            }
+        );
+    }
+
+    #[test]
+    fn file_range_from_unbounded_end() {
+        assert_eq!(
+            FileRange::from_bounds(4096..).unwrap(),
+            FileRange {
+                offset: 4096,
+                length: -1
+            }
+        );
+        assert_eq!(
+            FileRange::from_bounds(..).unwrap(),
+            FileRange {
+                offset: 0,
+                length: -1
+            }
+        );
+    }
+
+    #[test]
+    fn file_range_from_bounds_rejects_overflow() {
+        assert_eq!(
+            FileRange::from_bounds(0..=u64::MAX).unwrap_err().code(),
+            E_INVALIDARG
+        );
+        assert_eq!(
+            FileRange::from_bounds((Bound::Excluded(u64::MAX), Bound::Unbounded))
+                .unwrap_err()
+                .code(),
+            E_INVALIDARG
+        );
+    }
+}
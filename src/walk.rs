@@ -0,0 +1,83 @@
+use std::{
+    fs::{self, DirEntry, File, OpenOptions, ReadDir},
+    io,
+    os::windows::fs::OpenOptionsExt,
+    path::{Path, PathBuf},
+};
+
+use windows::Win32::Storage::FileSystem::{FILE_FLAG_BACKUP_SEMANTICS, FILE_READ_ATTRIBUTES};
+
+/// A lazy, depth-first iterator over every entry beneath a directory.
+///
+/// Note that reading a placeholder directory could invoke
+/// [SyncFilter::fetch_placeholders][crate::SyncFilter::fetch_placeholders].
+#[derive(Debug)]
+pub struct Walk {
+    root: Option<PathBuf>,
+    pending: Vec<ReadDir>,
+}
+
+impl Walk {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self {
+            root: Some(root.into()),
+            pending: Vec::new(),
+        }
+    }
+}
+
+impl Iterator for Walk {
+    type Item = io::Result<DirEntry>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(root) = self.root.take() {
+            match fs::read_dir(root) {
+                Ok(dir) => self.pending.push(dir),
+                Err(err) => return Some(Err(err)),
+            }
+        }
+
+        loop {
+            let entry = match self.pending.last_mut()?.next() {
+                Some(Ok(entry)) => entry,
+                Some(Err(err)) => return Some(Err(err)),
+                None => {
+                    self.pending.pop();
+                    continue;
+                }
+            };
+
+            // symlinks are not followed as the file type is not traversed
+            match entry.file_type() {
+                Ok(file_type) if file_type.is_dir() => match fs::read_dir(entry.path()) {
+                    Ok(dir) => self.pending.push(dir),
+                    Err(err) => return Some(Err(err)),
+                },
+                Ok(_) => {}
+                Err(err) => return Some(Err(err)),
+            }
+
+            return Some(Ok(entry));
+        }
+    }
+}
+
+/// Opens a file or directory with only enough access to query its attributes.
+///
+/// The handle does not have read access, so it will never cause a placeholder to be hydrated.
+pub fn open_attributes(path: &Path) -> io::Result<File> {
+    OpenOptions::new()
+        .access_mode(FILE_READ_ATTRIBUTES.0)
+        .custom_flags(FILE_FLAG_BACKUP_SEMANTICS.0)
+        .open(path)
+}
+
+/// Opens a file or directory with write access, as required to update a placeholder.
+///
+/// The handle does not have read access, so it will never cause a placeholder to be hydrated.
+pub fn open_write(path: &Path) -> io::Result<File> {
+    OpenOptions::new()
+        .write(true)
+        .custom_flags(FILE_FLAG_BACKUP_SEMANTICS.0)
+        .open(path)
+}
@@ -0,0 +1,87 @@
+use std::path::{Path, PathBuf};
+
+use widestring::{U16CStr, U16CString};
+use windows::{core, Win32::Foundation::E_INVALIDARG};
+
+/// A path encoded as a nul-terminated wide string, the form used by the operating system.
+///
+/// Paths that are already encoded, e.g. from WinRT APIs, could be wrapped without being decoded
+/// and encoded again.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct WidePath(U16CString);
+
+impl WidePath {
+    /// A reference to the encoded path.
+    pub fn as_u16cstr(&self) -> &U16CStr {
+        &self.0
+    }
+
+    /// Converts the [WidePath][crate::WidePath] to a [PathBuf][std::path::PathBuf].
+    pub fn to_path_buf(&self) -> PathBuf {
+        PathBuf::from(self.0.to_os_string())
+    }
+
+    pub(crate) fn into_inner(self) -> U16CString {
+        self.0
+    }
+}
+
+/// Returns `E_INVALIDARG` if the path contains a nul character.
+impl TryFrom<&Path> for WidePath {
+    type Error = core::Error;
+
+    fn try_from(path: &Path) -> core::Result<Self> {
+        U16CString::from_os_str(path).map(Self).map_err(|_| {
+            core::Error::new(
+                E_INVALIDARG,
+                "the path must not contain a null character".into(),
+            )
+        })
+    }
+}
+
+impl From<&U16CStr> for WidePath {
+    fn from(path: &U16CStr) -> Self {
+        Self(path.to_ucstring())
+    }
+}
+
+impl From<U16CString> for WidePath {
+    fn from(path: U16CString) -> Self {
+        Self(path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use widestring::u16cstr;
+
+    use super::*;
+
+    #[test]
+    fn try_from_path_round_trip() {
+        let path = Path::new("dir\\file.txt");
+        assert_eq!(WidePath::try_from(path).unwrap().to_path_buf(), path);
+    }
+
+    #[test]
+    fn try_from_path_rejects_nul() {
+        let err = WidePath::try_from(Path::new("file\0.txt")).unwrap_err();
+        assert_eq!(err.code(), E_INVALIDARG);
+    }
+
+    #[test]
+    fn from_u16cstr_round_trip() {
+        let wide = u16cstr!("dir\\file.txt");
+        let path = WidePath::from(wide);
+        assert_eq!(path.as_u16cstr(), wide);
+        assert_eq!(path.to_path_buf(), Path::new("dir\\file.txt"));
+    }
+
+    #[test]
+    fn from_u16cstring_is_not_reencoded() {
+        let wide = U16CString::from_str("file.txt").unwrap();
+        let ptr = wide.as_ptr();
+        assert_eq!(WidePath::from(wide).into_inner().as_ptr(), ptr);
+    }
+}